@@ -0,0 +1,295 @@
+use crate::token::Span;
+
+/// A flat run of encoded instructions: each is a one-byte opcode followed by its operands,
+/// big-endian, with the widths given by the opcode's `Definition`.
+pub type Instructions = Vec<u8>;
+
+/// Where a closure finds a variable it captures, in the function that creates it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capture {
+    /// A local slot of the creating call, shared with it until the call returns.
+    Local(usize),
+    /// A variable the creating closure captured itself.
+    Free(usize),
+    /// The creating closure, referred to by its own name.
+    Function,
+}
+
+/// Source spans of a run of instructions, for error reporting. Each entry holds the span of the
+/// instructions from its offset up to the offset of the next entry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    entries: Vec<(usize, Span)>,
+}
+
+impl SourceMap {
+    pub fn entries(&self) -> &[(usize, Span)] {
+        &self.entries
+    }
+
+    /// Records that the instructions from `offset` on were compiled from `span`.
+    pub fn add(&mut self, offset: usize, span: Span) {
+        if let Some(last) = self.entries.last_mut() {
+            if last.0 == offset {
+                last.1 = span;
+                return;
+            }
+            if last.1 == span {
+                return;
+            }
+        }
+
+        self.entries.push((offset, span));
+    }
+
+    /// Drops the entries for instructions at `offset` or later.
+    pub fn truncate(&mut self, offset: usize) {
+        self.entries.retain(|(start, _)| *start < offset);
+    }
+
+    pub fn lookup(&self, offset: usize) -> Option<Span> {
+        let index = self.entries.partition_point(|(start, _)| *start <= offset);
+        index.checked_sub(1).map(|index| self.entries[index].1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+    Constant,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    True,
+    False,
+    Null,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+    JumpNotTruthy,
+    Jump,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    GetBuiltin,
+    GetFree,
+    Array,
+    Hash,
+    Index,
+    Call,
+    ReturnValue,
+    Return,
+    Closure,
+    CurrentClosure,
+}
+
+/// The name and operand layout of an opcode. `operand_widths` holds the size in bytes of each
+/// operand, in order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    /// Every opcode, indexed by its byte value.
+    pub const ALL: [Opcode; 31] = [
+        Opcode::Constant,
+        Opcode::Pop,
+        Opcode::Add,
+        Opcode::Sub,
+        Opcode::Mul,
+        Opcode::Div,
+        Opcode::True,
+        Opcode::False,
+        Opcode::Null,
+        Opcode::Equal,
+        Opcode::NotEqual,
+        Opcode::GreaterThan,
+        Opcode::LessThan,
+        Opcode::Minus,
+        Opcode::Bang,
+        Opcode::JumpNotTruthy,
+        Opcode::Jump,
+        Opcode::GetGlobal,
+        Opcode::SetGlobal,
+        Opcode::GetLocal,
+        Opcode::SetLocal,
+        Opcode::GetBuiltin,
+        Opcode::GetFree,
+        Opcode::Array,
+        Opcode::Hash,
+        Opcode::Index,
+        Opcode::Call,
+        Opcode::ReturnValue,
+        Opcode::Return,
+        Opcode::Closure,
+        Opcode::CurrentClosure,
+    ];
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            // Index into the constant pool.
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            // Absolute offset of the jump target.
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            // Number of elements, and of keys plus values, taken off the stack.
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            // Number of arguments.
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            // Constant index of the compiled function; what it captures is listed on the function.
+            Opcode::Closure => ("OpClosure", &[2]),
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+        };
+
+        Definition { name, operand_widths }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        Opcode::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+/// Encodes one instruction. Operands are truncated to their width, so callers check the limits
+/// first.
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let widths = op.definition().operand_widths;
+    debug_assert_eq!(widths.len(), operands.len(), "wrong number of operands for {:?}", op);
+
+    let mut instruction = Vec::with_capacity(1 + widths.iter().sum::<usize>());
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+    }
+
+    instruction
+}
+
+/// Decodes the operands that follow an opcode, returning them with the number of bytes read.
+pub fn read_operands(definition: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(definition.operand_widths.len());
+    let mut offset = 0;
+
+    for width in definition.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+        offset += width;
+    }
+
+    (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes([ins[0], ins[1]])
+}
+
+#[cfg(test)]
+mod code_tests {
+    use crate::compiler::code::{make, read_operands, Opcode, SourceMap};
+    use crate::token::Span;
+
+    #[test]
+    fn test_make() {
+        let test_cases: Vec<(Opcode, Vec<usize>, Vec<u8>)> = vec![
+            (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (Opcode::GetLocal, vec![255], vec![Opcode::GetLocal as u8, 255]),
+            (Opcode::Closure, vec![65534], vec![Opcode::Closure as u8, 255, 254]),
+        ];
+
+        for (op, operands, expected) in test_cases {
+            assert_eq!(expected, make(op, &operands), "op: {:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let test_cases: Vec<(Opcode, Vec<usize>, usize)> = vec![
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::GetLocal, vec![255], 1),
+            (Opcode::Closure, vec![65535], 2),
+            (Opcode::Pop, vec![], 0),
+        ];
+
+        for (op, operands, bytes_read) in test_cases {
+            let instruction = make(op, &operands);
+            let definition = op.definition();
+
+            assert_eq!((operands, bytes_read), read_operands(&definition, &instruction[1..]), "op: {:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_source_map() {
+        let first = Span::new(0, 1, 1, 1);
+        let second = Span::new(4, 5, 1, 5);
+
+        let mut map = SourceMap::default();
+        map.add(0, first);
+        map.add(3, first);
+        map.add(4, second);
+        map.add(7, first);
+        map.truncate(7);
+        map.add(7, second);
+
+        assert_eq!([(0, first), (4, second)], map.entries());
+        assert_eq!(Some(first), map.lookup(3));
+        assert_eq!(Some(second), map.lookup(4));
+        assert_eq!(Some(second), map.lookup(100));
+        assert_eq!(None, SourceMap::default().lookup(0));
+    }
+
+    #[test]
+    fn test_opcode_bytes() {
+        for (byte, op) in Opcode::ALL.iter().enumerate() {
+            assert_eq!(byte, *op as usize);
+            assert_eq!(Ok(*op), Opcode::try_from(byte as u8));
+            assert!(op.definition().name.starts_with("Op"));
+        }
+
+        assert_eq!(Err(200), Opcode::try_from(200));
+    }
+}
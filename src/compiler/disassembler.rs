@@ -0,0 +1,167 @@
+use std::fmt::Write;
+
+use crate::compiler::code::{read_operands, Capture, Opcode};
+use crate::compiler::Bytecode;
+use crate::eval::object::Object;
+
+/// Lists `bytecode` for reading: one instruction per line with its offset, mnemonic and operands,
+/// and the value of any constant it refers to. The body of each function created with
+/// `OpClosure` follows the code that creates it, indented one level.
+///
+/// Malformed instructions are reported in the listing rather than panicking, so it can be used on
+/// bytecode read from a file.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = String::new();
+    write_instructions(&mut out, &bytecode.instructions, &bytecode.constants, 0);
+    out
+}
+
+fn write_instructions(out: &mut String, ins: &[u8], constants: &[Object], depth: usize) {
+    let indent = "    ".repeat(depth);
+    let mut functions = vec![];
+    let mut offset = 0;
+
+    while offset < ins.len() {
+        let op = match Opcode::try_from(ins[offset]) {
+            Ok(op) => op,
+            Err(byte) => {
+                let _ = writeln!(out, "{}{:04} ERROR: unknown opcode {}", indent, offset, byte);
+                return;
+            }
+        };
+
+        let definition = op.definition();
+        if offset + 1 + definition.operand_widths.iter().sum::<usize>() > ins.len() {
+            let _ = writeln!(out, "{}{:04} ERROR: {} is missing operands", indent, offset, definition.name);
+            return;
+        }
+
+        let (operands, read) = read_operands(&definition, &ins[offset + 1..]);
+        let mut text = definition.name.to_string();
+        for operand in &operands {
+            let _ = write!(text, " {}", operand);
+        }
+
+        match op {
+            Opcode::Constant | Opcode::Closure => {
+                let value = match constants.get(operands[0]) {
+                    Some(constant) => constant.inspect(),
+                    None => "ERROR: no such constant".to_string(),
+                };
+                let _ = writeln!(out, "{}{:04} {:<22}; {}", indent, offset, text, value);
+            }
+            _ => {
+                let _ = writeln!(out, "{}{:04} {}", indent, offset, text);
+            }
+        }
+
+        if op == Opcode::Closure {
+            functions.push(operands[0]);
+        }
+        offset += 1 + read;
+    }
+
+    for index in functions {
+        if let Some(Object::CompiledFunction(function)) = constants.get(index) {
+            let locals = if function.num_locals == 1 { "local" } else { "locals" };
+            let _ = writeln!(out);
+            let _ = write!(
+                out,
+                "{}constant {}: fn({}), {} {}",
+                indent,
+                index,
+                function.params.join(", "),
+                function.num_locals,
+                locals
+            );
+            if !function.captures.is_empty() {
+                let captures: Vec<String> = function.captures.iter().map(|capture| describe(*capture)).collect();
+                let _ = write!(out, ", captures {}", captures.join(", "));
+            }
+            let _ = writeln!(out);
+            write_instructions(out, &function.instructions, constants, depth + 1);
+        }
+    }
+}
+
+fn describe(capture: Capture) -> String {
+    match capture {
+        Capture::Local(index) => format!("local {}", index),
+        Capture::Free(index) => format!("free {}", index),
+        Capture::Function => "the enclosing function".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod disassembler_tests {
+    use crate::compiler::code::{make, Opcode};
+    use crate::compiler::disassembler::disassemble;
+    use crate::compiler::{Bytecode, Compiler};
+    use crate::eval::object::Object;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn compile(input: &str) -> Bytecode {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+        compiler.bytecode()
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let expected = "\
+0000 OpConstant 0          ; 1
+0003 OpSetGlobal 0
+0006 OpGetGlobal 0
+0009 OpMinus
+0010 OpConstant 1          ; \"two\"
+0013 OpMul
+0014 OpJumpNotTruthy 21
+0017 OpTrue
+0018 OpJump 22
+0021 OpNull
+0022 OpPop
+";
+        assert_eq!(expected, disassemble(&compile("let a = 1; if (-a * \"two\") { true }")));
+    }
+
+    #[test]
+    fn test_disassemble_nested_functions() {
+        let expected = "\
+0000 OpClosure 1           ; fn(x) { ... }
+0003 OpSetGlobal 0
+0006 OpGetGlobal 0
+0009 OpConstant 2          ; 2
+0012 OpCall 1
+0014 OpPop
+
+constant 1: fn(x), 1 local
+    0000 OpClosure 0           ; fn(y) { ... }
+    0003 OpReturnValue
+
+    constant 0: fn(y), 1 local, captures local 0
+        0000 OpGetFree 0
+        0002 OpGetLocal 0
+        0004 OpAdd
+        0005 OpReturnValue
+";
+        assert_eq!(expected, disassemble(&compile("let adder = fn(x) { fn(y) { x + y } }; adder(2)")));
+    }
+
+    #[test]
+    fn test_disassemble_malformed() {
+        let bytecode = |instructions: Vec<u8>| Bytecode {
+            instructions,
+            constants: vec![Object::Integer(1)],
+            source_map: Default::default(),
+            globals: vec![],
+        };
+
+        let truncated = [make(Opcode::Constant, &[0]), vec![Opcode::Jump as u8, 0]].concat();
+        assert_eq!("0000 OpConstant 0          ; 1\n0003 ERROR: OpJump is missing operands\n", disassemble(&bytecode(truncated)));
+
+        assert_eq!("0000 OpPop\n0001 ERROR: unknown opcode 255\n", disassemble(&bytecode(vec![Opcode::Pop as u8, 255])));
+        assert_eq!("0000 OpConstant 7          ; ERROR: no such constant\n", disassemble(&bytecode(make(Opcode::Constant, &[7]))));
+    }
+}
@@ -0,0 +1,481 @@
+//! The `.mkc` file format, for running compiled programs without lexing or parsing them again.
+//!
+//! A file starts with a header of the magic bytes, the format version (`u16`) and a CRC-32
+//! checksum (`u32`) of the rest of the file. The rest holds, in order:
+//!
+//! - the constant pool: a count, then one tagged entry per constant;
+//! - the top-level instructions;
+//! - the names of the global slots, a count and then one string per slot;
+//! - the debug line table: the name of the source file and the source map of the top level.
+//!
+//! Compiled functions carry their own instructions, captured variables and source map inside the
+//! constant pool.
+//! Numbers are big-endian like instruction operands; lengths and counts are `u32`.
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+use crate::compiler::code::{read_operands, Capture, Opcode, SourceMap};
+use crate::compiler::Bytecode;
+use crate::eval::builtins::BUILTINS;
+use crate::eval::object::{CompiledFunction, Object};
+use crate::token::Span;
+
+pub const MAGIC: &[u8; 4] = b"\x7fMKC";
+
+/// Version of the format and of the instruction set it holds. Bump it whenever either changes,
+/// including adding or reordering opcodes, so older files are turned away instead of misread.
+pub const VERSION: u16 = 3;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+const TAG_CAPTURE_LOCAL: u8 = 0;
+const TAG_CAPTURE_FREE: u8 = 1;
+const TAG_CAPTURE_FUNCTION: u8 = 2;
+
+/// Error raised while loading a `.mkc` file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    NotCompiled,
+    IncompatibleVersion(u16),
+    ChecksumMismatch,
+    Malformed(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotCompiled => write!(f, "not a compiled Monkey program"),
+            LoadError::IncompatibleVersion(version) => write!(
+                f,
+                "compiled with bytecode version {}, but this build runs version {}; compile the program again",
+                version, VERSION
+            ),
+            LoadError::ChecksumMismatch => write!(f, "checksum mismatch: the file is corrupted"),
+            LoadError::Malformed(message) => write!(f, "malformed bytecode: {}", message),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// Encodes `bytecode` compiled from the file `origin`, which the line table keeps so runtime errors
+/// can point at it.
+pub fn encode(bytecode: &Bytecode, origin: &str) -> Vec<u8> {
+    let mut body = vec![];
+    write_u32(&mut body, bytecode.constants.len());
+    for constant in &bytecode.constants {
+        write_constant(&mut body, constant);
+    }
+    write_bytes(&mut body, &bytecode.instructions);
+    write_u32(&mut body, bytecode.globals.len());
+    for name in &bytecode.globals {
+        write_bytes(&mut body, name.as_bytes());
+    }
+    write_bytes(&mut body, origin.as_bytes());
+    write_source_map(&mut body, &bytecode.source_map);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_be_bytes());
+    out.extend_from_slice(&crc32(&body).to_be_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+/// Decodes a file written by `encode`, giving back the bytecode and the name of its source file.
+/// The instructions are checked to decode and to refer to constants, builtins, variables and jump
+/// targets that exist, so the VM can run them. Their stack effects are not: the VM reports an
+/// instruction that takes more values than were pushed.
+pub fn decode(bytes: &[u8]) -> Result<(Bytecode, String), LoadError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::NotCompiled);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(LoadError::Malformed("the header is truncated".to_string()));
+    }
+
+    let version = u16::from_be_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(LoadError::IncompatibleVersion(version));
+    }
+
+    let checksum = u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+    let body = &bytes[HEADER_LEN..];
+    if crc32(body) != checksum {
+        return Err(LoadError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader { bytes: body, offset: 0 };
+    let mut constants = vec![];
+    for _ in 0..reader.u32()? {
+        constants.push(reader.constant()?);
+    }
+    let instructions = reader.bytes()?.to_vec();
+    let mut globals = vec![];
+    for _ in 0..reader.u32()? {
+        globals.push(reader.string()?);
+    }
+    let origin = reader.string()?;
+    let source_map = reader.source_map()?;
+
+    if reader.offset != body.len() {
+        return Err(LoadError::Malformed("unexpected data after the line table".to_string()));
+    }
+
+    let bytecode = Bytecode { instructions, constants, source_map, globals };
+    validate(&bytecode, &bytecode.instructions, 0, 0)?;
+    for constant in &bytecode.constants {
+        if let Object::CompiledFunction(function) = constant {
+            validate(&bytecode, &function.instructions, function.num_locals, function.captures.len())?;
+        }
+    }
+
+    Ok((bytecode, origin))
+}
+
+/// Checks the instructions `ins` of a function in `bytecode` with `num_locals` locals and
+/// `num_free` captured variables; the top level has neither.
+fn validate(bytecode: &Bytecode, ins: &[u8], num_locals: usize, num_free: usize) -> Result<(), LoadError> {
+    let constants = &bytecode.constants;
+    let malformed = |offset: usize, message: String| Err(LoadError::Malformed(format!("{} at offset {}", message, offset)));
+
+    let mut starts = vec![];
+    let mut jumps = vec![];
+    let mut offset = 0;
+    while offset < ins.len() {
+        starts.push(offset);
+        let op = match Opcode::try_from(ins[offset]) {
+            Ok(op) => op,
+            Err(byte) => return malformed(offset, format!("unknown opcode {}", byte)),
+        };
+
+        let definition = op.definition();
+        if offset + 1 + definition.operand_widths.iter().sum::<usize>() > ins.len() {
+            return malformed(offset, format!("{} is missing operands", definition.name));
+        }
+        let (operands, read) = read_operands(&definition, &ins[offset + 1..]);
+
+        match op {
+            Opcode::Constant if operands[0] >= constants.len() => {
+                return malformed(offset, format!("no constant {}", operands[0]));
+            }
+            Opcode::Closure => {
+                let function = match constants.get(operands[0]) {
+                    Some(Object::CompiledFunction(function)) => function,
+                    _ => return malformed(offset, format!("constant {} is not a function", operands[0])),
+                };
+                for capture in &function.captures {
+                    match *capture {
+                        Capture::Local(index) if index >= num_locals => {
+                            return malformed(offset, format!("capture of missing local {}", index));
+                        }
+                        Capture::Free(index) if index >= num_free => {
+                            return malformed(offset, format!("capture of missing free variable {}", index));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Opcode::GetGlobal | Opcode::SetGlobal if operands[0] >= bytecode.globals.len() => {
+                return malformed(offset, format!("no global {}", operands[0]));
+            }
+            Opcode::GetLocal | Opcode::SetLocal if operands[0] >= num_locals => {
+                return malformed(offset, format!("no local {}", operands[0]));
+            }
+            Opcode::GetFree if operands[0] >= num_free => {
+                return malformed(offset, format!("no free variable {}", operands[0]));
+            }
+            Opcode::GetBuiltin if operands[0] >= BUILTINS.len() => {
+                return malformed(offset, format!("no builtin {}", operands[0]));
+            }
+            Opcode::Jump | Opcode::JumpNotTruthy if operands[0] > ins.len() => {
+                return malformed(offset, format!("jump past the end to {}", operands[0]));
+            }
+            Opcode::Jump | Opcode::JumpNotTruthy => jumps.push((offset, operands[0])),
+            _ => {}
+        }
+
+        offset += 1 + read;
+    }
+
+    for (offset, target) in jumps {
+        if target < ins.len() && starts.binary_search(&target).is_err() {
+            return malformed(offset, format!("jump to {} inside an instruction", target));
+        }
+    }
+
+    Ok(())
+}
+
+/// CRC-32 as used by zlib and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn write_constant(out: &mut Vec<u8>, constant: &Object) {
+    match constant {
+        Object::Integer(value) => {
+            out.push(TAG_INTEGER);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+        Object::Str(value) => {
+            out.push(TAG_STRING);
+            write_bytes(out, value.as_bytes());
+        }
+        Object::CompiledFunction(function) => {
+            out.push(TAG_FUNCTION);
+            write_u32(out, function.num_locals);
+            write_u32(out, function.params.len());
+            for param in &function.params {
+                write_bytes(out, param.as_bytes());
+            }
+            write_bytes(out, &function.instructions);
+            write_u32(out, function.captures.len());
+            for capture in &function.captures {
+                let (tag, index) = match *capture {
+                    Capture::Local(index) => (TAG_CAPTURE_LOCAL, index),
+                    Capture::Free(index) => (TAG_CAPTURE_FREE, index),
+                    Capture::Function => (TAG_CAPTURE_FUNCTION, 0),
+                };
+                out.push(tag);
+                write_u32(out, index);
+            }
+            write_source_map(out, &function.source_map);
+        }
+        other => unreachable!("the compiler does not put {} values in the constant pool", other.type_name()),
+    }
+}
+
+fn write_source_map(out: &mut Vec<u8>, source_map: &SourceMap) {
+    write_u32(out, source_map.entries().len());
+    for (offset, span) in source_map.entries() {
+        for value in [*offset, span.start, span.end, span.line, span.col] {
+            write_u32(out, value);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        match self.bytes.get(self.offset..self.offset.saturating_add(len)) {
+            Some(bytes) => {
+                self.offset += len;
+                Ok(bytes)
+            }
+            None => Err(LoadError::Malformed("unexpected end of file".to_string())),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, LoadError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], LoadError> {
+        let len = self.u32()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| LoadError::Malformed("a string is not valid UTF-8".to_string()))
+    }
+
+    fn constant(&mut self) -> Result<Object, LoadError> {
+        match self.u8()? {
+            TAG_INTEGER => {
+                let bytes = self.take(8)?;
+                let mut value = [0; 8];
+                value.copy_from_slice(bytes);
+                Ok(Object::Integer(i64::from_be_bytes(value)))
+            }
+            TAG_STRING => Ok(Object::Str(self.string()?)),
+            TAG_FUNCTION => {
+                let num_locals = self.u32()?;
+                let mut params = vec![];
+                for _ in 0..self.u32()? {
+                    params.push(self.string()?);
+                }
+                let instructions = self.bytes()?.to_vec();
+                let mut captures = vec![];
+                for _ in 0..self.u32()? {
+                    captures.push(self.capture()?);
+                }
+                let source_map = self.source_map()?;
+
+                let function = CompiledFunction { instructions, num_locals, params, captures, source_map };
+                Ok(Object::CompiledFunction(Rc::new(function)))
+            }
+            tag => Err(LoadError::Malformed(format!("unknown constant tag {}", tag))),
+        }
+    }
+
+    fn capture(&mut self) -> Result<Capture, LoadError> {
+        let tag = self.u8()?;
+        let index = self.u32()?;
+        match tag {
+            TAG_CAPTURE_LOCAL => Ok(Capture::Local(index)),
+            TAG_CAPTURE_FREE => Ok(Capture::Free(index)),
+            TAG_CAPTURE_FUNCTION => Ok(Capture::Function),
+            tag => Err(LoadError::Malformed(format!("unknown capture tag {}", tag))),
+        }
+    }
+
+    fn source_map(&mut self) -> Result<SourceMap, LoadError> {
+        let mut source_map = SourceMap::default();
+        for _ in 0..self.u32()? {
+            let offset = self.u32()?;
+            let span = Span::new(self.u32()?, self.u32()?, self.u32()?, self.u32()?);
+            source_map.add(offset, span);
+        }
+        Ok(source_map)
+    }
+}
+
+#[cfg(test)]
+mod file_tests {
+    use std::rc::Rc;
+
+    use crate::compiler::code::{make, Opcode};
+    use crate::compiler::file::{crc32, decode, encode, LoadError, HEADER_LEN, VERSION};
+    use crate::compiler::Bytecode;
+    use crate::eval::object::{CompiledFunction, Object};
+    use crate::runner;
+    use crate::vm::Vm;
+
+    fn compile(input: &str) -> Bytecode {
+        runner::compile(&runner::parse(input).unwrap()).unwrap()
+    }
+
+    /// Rewrites the checksum after tampering with the body, to get past the check.
+    fn reseal(bytes: &mut [u8]) {
+        let checksum = crc32(&bytes[HEADER_LEN..]);
+        bytes[6..HEADER_LEN].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = "let adder = fn(x) { fn(y) { x + y } };\nlet greet = fn(name) { \"hi \" + name };\n[adder(-2)(3), greet(\"you\")]";
+        let bytecode = compile(input);
+
+        let (decoded, origin) = decode(&encode(&bytecode, "adder.mk")).unwrap();
+        assert_eq!("adder.mk", origin);
+        assert_eq!(bytecode, decoded);
+        assert_eq!(bytecode.source_map, decoded.source_map);
+        for (constant, decoded) in bytecode.constants.iter().zip(&decoded.constants) {
+            if let (Object::CompiledFunction(function), Object::CompiledFunction(decoded)) = (constant, decoded) {
+                assert_eq!(function.source_map, decoded.source_map);
+            }
+        }
+
+        assert_eq!("[1, \"hi you\"]", Vm::new(decoded).run().unwrap().inspect());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = encode(&compile("let x = 1; x + 2"), "x.mk");
+
+        assert_eq!(Err(LoadError::NotCompiled), decode(b"let x = 1;"));
+        assert_eq!(Err(LoadError::NotCompiled), decode(b""));
+
+        let mut old = bytes.clone();
+        old[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+        assert_eq!(Err(LoadError::IncompatibleVersion(VERSION + 1)), decode(&old));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(Err(LoadError::ChecksumMismatch), decode(&corrupted));
+
+        let mut truncated = bytes[..bytes.len() - 3].to_vec();
+        reseal(&mut truncated);
+        assert_eq!(Err(LoadError::Malformed("unexpected end of file".to_string())), decode(&truncated));
+    }
+
+    #[test]
+    fn test_decode_validates_instructions() {
+        let bytecode = |instructions: Vec<u8>| Bytecode {
+            instructions,
+            constants: vec![Object::Integer(1)],
+            source_map: Default::default(),
+            globals: vec![],
+        };
+        let error = |instructions: Vec<u8>| decode(&encode(&bytecode(instructions), "x.mk")).unwrap_err().to_string();
+
+        assert_eq!("malformed bytecode: unknown opcode 255 at offset 0", error(vec![255]));
+        assert_eq!("malformed bytecode: OpConstant is missing operands at offset 0", error(vec![Opcode::Constant as u8, 0]));
+        assert_eq!("malformed bytecode: no constant 1 at offset 0", error(make(Opcode::Constant, &[1])));
+        assert_eq!("malformed bytecode: no local 0 at offset 0", error(make(Opcode::GetLocal, &[0])));
+        assert_eq!("malformed bytecode: no global 0 at offset 0", error(make(Opcode::GetGlobal, &[0])));
+        assert_eq!(
+            "malformed bytecode: jump to 1 inside an instruction at offset 3",
+            error([make(Opcode::Constant, &[0]), make(Opcode::Jump, &[1])].concat())
+        );
+        assert_eq!("malformed bytecode: constant 0 is not a function at offset 0", error(make(Opcode::Closure, &[0])));
+    }
+
+    #[test]
+    fn test_run_unbalanced_bytecode() {
+        // Takes more values than it pushes, which `validate` does not look for.
+        let function = CompiledFunction {
+            instructions: [make(Opcode::Pop, &[]), make(Opcode::Return, &[])].concat(),
+            num_locals: 0,
+            params: vec![],
+            captures: vec![],
+            source_map: Default::default(),
+        };
+        let run = |instructions: Vec<Vec<u8>>| {
+            let bytecode = Bytecode {
+                instructions: instructions.concat(),
+                constants: vec![Object::Integer(1), Object::CompiledFunction(Rc::new(function.clone()))],
+                source_map: Default::default(),
+                globals: vec![],
+            };
+            let (decoded, _) = decode(&encode(&bytecode, "x.mk")).unwrap();
+            Vm::new(decoded).run().unwrap_err().message
+        };
+
+        assert_eq!("stack underflow", run(vec![make(Opcode::Pop, &[])]));
+        assert_eq!("stack underflow", run(vec![make(Opcode::Constant, &[0]), make(Opcode::Add, &[])]));
+        assert_eq!("stack underflow", run(vec![make(Opcode::Constant, &[0]), make(Opcode::Array, &[2])]));
+        assert_eq!("stack underflow", run(vec![make(Opcode::Call, &[0])]));
+        // The function may not pop the values of its caller.
+        assert_eq!("stack underflow", run(vec![make(Opcode::Constant, &[0]), make(Opcode::Closure, &[1]), make(Opcode::Call, &[0])]));
+    }
+}
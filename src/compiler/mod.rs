@@ -0,0 +1,981 @@
+pub mod code;
+pub mod disassembler;
+pub mod file;
+pub mod symbol_table;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+use std::mem;
+use std::rc::Rc;
+
+use crate::compiler::code::{make, Capture, Instructions, Opcode, SourceMap};
+use crate::compiler::symbol_table::{Symbol, SymbolScope, SymbolTable};
+use crate::eval::object::{CompiledFunction, Object};
+use crate::parser::ast::{BlockStatement, Expr, ExprKind, Ident, InfixOperator, PrefixOperator, Program, Stmt, StmtKind};
+use crate::token::Span;
+
+/// Operand written for a jump whose target is not known yet; `change_operand` patches it later.
+const PLACEHOLDER: usize = 9999;
+
+/// Compiled program: the instructions of the top level and the constants they refer to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    pub source_map: SourceMap,
+    /// Names of the global slots, by index, for reporting a read of one that was never set.
+    pub globals: Vec<String>,
+}
+
+/// Error raised while compiling a program, such as one with more bindings than an operand can
+/// address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl CompileError {
+    pub fn new(message: String, span: Span) -> CompileError {
+        CompileError { message, span }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CompileError {}
+
+#[derive(Clone, Copy, Debug)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+/// Instructions of the function being compiled, with the last two emitted so a trailing `OpPop`
+/// can be dropped or turned into a return.
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    source_map: SourceMap,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    /// Names the function binds more than once, counting its parameters.
+    rebound: HashSet<String>,
+}
+
+/// Lowers a `Program` to bytecode for a stack machine. Globals live in numbered slots, locals in
+/// the slots of their call frame, and every function literal becomes a `CompiledFunction`
+/// constant that `OpClosure` pairs with the free variables it captures.
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    /// Span of the innermost node being compiled, recorded for each instruction emitted.
+    span: Span,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler::new_with_state(SymbolTable::with_builtins(), vec![])
+    }
+
+    /// Continues from the globals and constants of an earlier compilation, so separately compiled
+    /// inputs can refer to each other's bindings.
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object>) -> Compiler {
+        Compiler { constants, symbol_table, scopes: vec![CompilationScope::default()], span: Span::default() }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), CompileError> {
+        for stmt in &program.statements {
+            self.compile_stmt(stmt)?;
+        }
+
+        // A program evaluates to its last statement, and `let` evaluates to null.
+        if let Some(Stmt { kind: StmtKind::Let(_), .. }) = program.statements.last() {
+            self.emit(Opcode::Null, &[]);
+            self.emit(Opcode::Pop, &[]);
+        }
+
+        Ok(())
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        let scope = self.scopes.last().expect("the top level scope is never left");
+
+        Bytecode {
+            instructions: scope.instructions.clone(),
+            constants: self.constants.clone(),
+            source_map: scope.source_map.clone(),
+            globals: self.symbol_table.global_names(),
+        }
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        let outer = mem::replace(&mut self.span, stmt.span);
+        let result = self.compile_stmt_kind(stmt);
+        self.span = outer;
+        result
+    }
+
+    fn compile_stmt_kind(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                match &let_stmt.expr.kind {
+                    // The body can refer to itself as the closure being run, rather than through
+                    // the binding, only if nothing can rebind the name: a global may be bound
+                    // again by any later code.
+                    ExprKind::Function { params, body } => {
+                        let name = &let_stmt.ident.name;
+                        let fixed = !self.symbol_table.is_global() && !self.current_scope().rebound.contains(name);
+                        self.compile_function(fixed.then_some(name), params, body, let_stmt.expr.span)?
+                    }
+                    _ => self.compile_expr(&let_stmt.expr)?,
+                }
+
+                let symbol = self.define(&let_stmt.ident)?;
+                match symbol.scope {
+                    SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+                    _ => self.emit(Opcode::SetLocal, &[symbol.index]),
+                };
+            }
+            StmtKind::Return(Some(expr)) => {
+                self.compile_expr(expr)?;
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            StmtKind::Return(None) => {
+                self.emit(Opcode::Return, &[]);
+            }
+            StmtKind::Expr(expr) => {
+                self.compile_expr(expr)?;
+                self.emit(Opcode::Pop, &[]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_block(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        for stmt in &block.statements {
+            self.compile_stmt(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        let outer = mem::replace(&mut self.span, expr.span);
+        let result = self.compile_expr_kind(expr);
+        self.span = outer;
+        result
+    }
+
+    fn compile_expr_kind(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match &expr.kind {
+            ExprKind::Ident(ident) => {
+                // As in the evaluator, a name only has to be bound by the time the code using it
+                // runs, so functions can refer to globals defined after them.
+                let symbol = match self.symbol_table.resolve(&ident.name) {
+                    Some(symbol) => symbol,
+                    None => self.define_global(ident)?,
+                };
+                self.load_symbol(&symbol);
+            }
+            ExprKind::IntLiteral(value) => {
+                let index = self.add_constant(Object::Integer(*value), expr.span)?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            ExprKind::StringLiteral(value) => {
+                let index = self.add_constant(Object::Str(value.clone()), expr.span)?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            ExprKind::Boolean(true) => {
+                self.emit(Opcode::True, &[]);
+            }
+            ExprKind::Boolean(false) => {
+                self.emit(Opcode::False, &[]);
+            }
+            ExprKind::PrefixExpr { expr, operator } => {
+                self.compile_expr(expr)?;
+
+                match operator {
+                    Some(PrefixOperator::Not) => self.emit(Opcode::Bang, &[]),
+                    Some(PrefixOperator::Negate) => self.emit(Opcode::Minus, &[]),
+                    None => return Ok(()),
+                };
+            }
+            ExprKind::InfixExpr { left, right, operator } => {
+                self.compile_expr(left)?;
+                self.compile_expr(right)?;
+
+                // `<` gets its own opcode rather than swapping the operands of `>`, which would
+                // evaluate them right to left.
+                let opcode = match operator {
+                    InfixOperator::Add => Opcode::Add,
+                    InfixOperator::Sub => Opcode::Sub,
+                    InfixOperator::Mul => Opcode::Mul,
+                    InfixOperator::Div => Opcode::Div,
+                    InfixOperator::Equals => Opcode::Equal,
+                    InfixOperator::NotEquals => Opcode::NotEqual,
+                    InfixOperator::LessThan => Opcode::LessThan,
+                    InfixOperator::GreaterThan => Opcode::GreaterThan,
+                };
+                self.emit(opcode, &[]);
+            }
+            ExprKind::If { condition, consequence, alternative } => {
+                self.compile_expr(condition)?;
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER]);
+
+                self.compile_branch(consequence)?;
+                let jump = self.emit(Opcode::Jump, &[PLACEHOLDER]);
+
+                let after_consequence = self.jump_target(expr.span)?;
+                self.change_operand(jump_not_truthy, after_consequence);
+
+                match alternative {
+                    Some(alternative) => self.compile_branch(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[]);
+                    }
+                }
+
+                let after_alternative = self.jump_target(expr.span)?;
+                self.change_operand(jump, after_alternative);
+            }
+            ExprKind::Function { params, body } => self.compile_function(None, params, body, expr.span)?,
+            ExprKind::Call { callee, args } => {
+                self.compile_expr(callee)?;
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+
+                let count = check_limit(args.len(), u8::MAX as usize, "arguments", expr.span)?;
+                self.emit(Opcode::Call, &[count]);
+            }
+            ExprKind::Array(elements) => {
+                for element in elements {
+                    self.compile_expr(element)?;
+                }
+
+                let count = check_limit(elements.len(), u16::MAX as usize, "array elements", expr.span)?;
+                self.emit(Opcode::Array, &[count]);
+            }
+            ExprKind::Index { target, index } => {
+                self.compile_expr(target)?;
+                self.compile_expr(index)?;
+                self.emit(Opcode::Index, &[]);
+            }
+            ExprKind::Hash(pairs) => {
+                // Pairs stay in source order: keys and values are evaluated left to right and a
+                // repeated key keeps its last value.
+                for (key, value) in pairs {
+                    self.compile_expr(key)?;
+                    self.compile_expr(value)?;
+                }
+
+                let count = check_limit(pairs.len() * 2, u16::MAX as usize, "hash entries", expr.span)?;
+                self.emit(Opcode::Hash, &[count]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the block of an `if` so that it leaves its value on the stack: the value of its
+    /// last expression, or null when it ends in a `let` or is empty.
+    fn compile_branch(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        self.compile_block(block)?;
+
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::Null, &[]);
+        }
+
+        Ok(())
+    }
+
+    fn compile_function(
+        &mut self,
+        name: Option<&str>,
+        params: &[Ident],
+        body: &BlockStatement,
+        span: Span,
+    ) -> Result<(), CompileError> {
+        self.enter_scope();
+
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for param in params {
+            self.define(param)?;
+        }
+        let mut bound: HashSet<&str> = params.iter().map(|param| param.name.as_str()).collect();
+        for ident in let_bindings(body) {
+            self.declare(ident)?;
+            if !bound.insert(&ident.name) {
+                self.current_scope().rebound.insert(ident.name.clone());
+            }
+        }
+
+        self.compile_block(body)?;
+
+        if self.last_instruction_is(Opcode::Pop) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(Opcode::ReturnValue) && !self.last_instruction_is(Opcode::Return) {
+            self.emit(Opcode::Return, &[]);
+        }
+
+        // Free symbols keep the symbol they resolved to in the enclosing scope, which is where
+        // the closure finds them when it is created.
+        let captures: Vec<Capture> = self
+            .symbol_table
+            .free_symbols()
+            .iter()
+            .map(|symbol| match symbol.scope {
+                SymbolScope::Local => Capture::Local(symbol.index),
+                SymbolScope::Free => Capture::Free(symbol.index),
+                SymbolScope::Function => Capture::Function,
+                scope => unreachable!("{:?} symbols are never captured", scope),
+            })
+            .collect();
+        check_limit(captures.len(), u8::MAX as usize, "captured variables", span)?;
+
+        let num_locals = self.symbol_table.num_definitions();
+        let scope = self.leave_scope();
+
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            num_locals,
+            params: params.iter().map(|param| param.name.clone()).collect(),
+            captures,
+            source_map: scope.source_map,
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)), span)?;
+        self.emit(Opcode::Closure, &[index]);
+
+        Ok(())
+    }
+
+    /// Binds `ident` in the current scope, failing if its slot would not fit in an operand.
+    fn define(&mut self, ident: &Ident) -> Result<Symbol, CompileError> {
+        let symbol = self.symbol_table.define(&ident.name);
+
+        match symbol.scope {
+            SymbolScope::Global => check_limit(symbol.index, u16::MAX as usize, "global bindings", ident.span)?,
+            _ => check_limit(symbol.index, u8::MAX as usize, "local bindings", ident.span)?,
+        };
+
+        Ok(symbol)
+    }
+
+    /// Sets aside a local slot for `ident`, bound further on in the function being compiled.
+    fn declare(&mut self, ident: &Ident) -> Result<(), CompileError> {
+        self.symbol_table.declare(&ident.name);
+        check_limit(self.symbol_table.num_definitions(), u8::MAX as usize + 1, "local bindings", ident.span)?;
+
+        Ok(())
+    }
+
+    /// Binds `ident` as a global on its first use, before anything binds it.
+    fn define_global(&mut self, ident: &Ident) -> Result<Symbol, CompileError> {
+        let symbol = self.symbol_table.define_global(&ident.name);
+        check_limit(symbol.index, u16::MAX as usize, "global bindings", ident.span)?;
+
+        Ok(symbol)
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            SymbolScope::Builtin => self.emit(Opcode::GetBuiltin, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+        };
+    }
+
+    fn add_constant(&mut self, object: Object, span: Span) -> Result<usize, CompileError> {
+        let index = check_limit(self.constants.len(), u16::MAX as usize, "constants", span)?;
+        self.constants.push(object);
+        Ok(index)
+    }
+
+    /// The offset of the next instruction, as the target of a jump.
+    fn jump_target(&self, span: Span) -> Result<usize, CompileError> {
+        check_limit(self.current_instructions().len(), u16::MAX as usize, "instructions to jump over", span)
+    }
+
+    fn current_scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("the top level scope is never left")
+    }
+
+    fn current_instructions(&self) -> &Instructions {
+        &self.scopes.last().expect("the top level scope is never left").instructions
+    }
+
+    /// Appends an instruction and returns its position.
+    fn emit(&mut self, opcode: Opcode, operands: &[usize]) -> usize {
+        let instruction = make(opcode, operands);
+
+        let span = self.span;
+        let scope = self.current_scope();
+        let position = scope.instructions.len();
+        scope.instructions.extend(instruction);
+        scope.source_map.add(position, span);
+
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction { opcode, position });
+
+        position
+    }
+
+    fn last_instruction_is(&self, opcode: Opcode) -> bool {
+        let scope = self.scopes.last().expect("the top level scope is never left");
+        matches!(scope.last_instruction, Some(last) if last.opcode == opcode)
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.current_scope();
+        if let Some(last) = scope.last_instruction {
+            scope.instructions.truncate(last.position);
+            scope.source_map.truncate(last.position);
+            scope.last_instruction = scope.previous_instruction;
+        }
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let scope = self.current_scope();
+        if let Some(last) = scope.last_instruction.as_mut() {
+            scope.instructions[last.position] = Opcode::ReturnValue as u8;
+            last.opcode = Opcode::ReturnValue;
+        }
+    }
+
+    /// Rewrites the operand of the instruction at `position`, to point a jump at its target.
+    fn change_operand(&mut self, position: usize, operand: usize) {
+        let scope = self.current_scope();
+        let opcode = Opcode::try_from(scope.instructions[position]).expect("position is at an opcode");
+        let instruction = make(opcode, &[operand]);
+
+        scope.instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        self.symbol_table = SymbolTable::new_enclosed(mem::take(&mut self.symbol_table));
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let scope = self.scopes.pop().expect("leave_scope without enter_scope");
+        self.symbol_table = mem::take(&mut self.symbol_table).into_outer().expect("leave_scope without enter_scope");
+        scope
+    }
+}
+
+/// The names `block` binds with `let`, including in the blocks of `if` expressions, which bind in
+/// the enclosing function too. Function literals have scopes of their own and are skipped.
+fn let_bindings(block: &BlockStatement) -> Vec<&Ident> {
+    let mut idents = vec![];
+    for stmt in &block.statements {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                idents.push(&let_stmt.ident);
+                expr_let_bindings(&let_stmt.expr, &mut idents);
+            }
+            StmtKind::Return(Some(expr)) | StmtKind::Expr(expr) => expr_let_bindings(expr, &mut idents),
+            StmtKind::Return(None) => {}
+        }
+    }
+    idents
+}
+
+fn expr_let_bindings<'a>(expr: &'a Expr, idents: &mut Vec<&'a Ident>) {
+    match &expr.kind {
+        ExprKind::PrefixExpr { expr, .. } => expr_let_bindings(expr, idents),
+        ExprKind::InfixExpr { left, right, .. } => {
+            expr_let_bindings(left, idents);
+            expr_let_bindings(right, idents);
+        }
+        ExprKind::If { condition, consequence, alternative } => {
+            expr_let_bindings(condition, idents);
+            idents.extend(let_bindings(consequence));
+            if let Some(alternative) = alternative {
+                idents.extend(let_bindings(alternative));
+            }
+        }
+        ExprKind::Call { callee, args } => {
+            expr_let_bindings(callee, idents);
+            args.iter().for_each(|arg| expr_let_bindings(arg, idents));
+        }
+        ExprKind::Array(elements) => elements.iter().for_each(|element| expr_let_bindings(element, idents)),
+        ExprKind::Index { target, index } => {
+            expr_let_bindings(target, idents);
+            expr_let_bindings(index, idents);
+        }
+        ExprKind::Hash(pairs) => pairs.iter().for_each(|(key, value)| {
+            expr_let_bindings(key, idents);
+            expr_let_bindings(value, idents);
+        }),
+        ExprKind::Ident(_)
+        | ExprKind::IntLiteral(_)
+        | ExprKind::StringLiteral(_)
+        | ExprKind::Boolean(_)
+        | ExprKind::Function { .. } => {}
+    }
+}
+
+fn check_limit(value: usize, max: usize, what: &str, span: Span) -> Result<usize, CompileError> {
+    if value > max {
+        return Err(CompileError::new(format!("too many {}: the limit is {}", what, max), span));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod compiler_tests {
+    use std::rc::Rc;
+
+    use crate::compiler::code::{make, Capture, Instructions, Opcode, SourceMap};
+    use crate::compiler::{Bytecode, Compiler};
+    use crate::eval::object::{CompiledFunction, Object};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::token::Span;
+
+    fn compile(input: &str) -> Bytecode {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+
+        let mut compiler = Compiler::new();
+        if let Err(e) = compiler.compile(&program) {
+            panic!("input: {}, compile error: {}", input, e);
+        }
+        compiler.bytecode()
+    }
+
+    fn assert_compiles(input: &str, constants: Vec<Object>, instructions: Vec<Instructions>) {
+        let bytecode = compile(input);
+
+        assert_eq!(instructions.concat(), bytecode.instructions, "input: {}", input);
+        assert_eq!(constants, bytecode.constants, "input: {}", input);
+    }
+
+    fn int(value: i64) -> Object {
+        Object::Integer(value)
+    }
+
+    fn function(params: &[&str], num_locals: usize, instructions: Vec<Instructions>) -> Object {
+        closure(params, num_locals, &[], instructions)
+    }
+
+    fn closure(params: &[&str], num_locals: usize, captures: &[Capture], instructions: Vec<Instructions>) -> Object {
+        Object::CompiledFunction(Rc::new(CompiledFunction {
+            instructions: instructions.concat(),
+            num_locals,
+            params: params.iter().map(|param| param.to_string()).collect(),
+            captures: captures.to_vec(),
+            source_map: SourceMap::default(),
+        }))
+    }
+
+    #[test]
+    fn test_infix_operators() {
+        let test_cases: Vec<(&str, Opcode)> = vec![
+            ("1 + 2", Opcode::Add),
+            ("1 - 2", Opcode::Sub),
+            ("1 * 2", Opcode::Mul),
+            ("1 / 2", Opcode::Div),
+            ("1 == 2", Opcode::Equal),
+            ("1 != 2", Opcode::NotEqual),
+            ("1 < 2", Opcode::LessThan),
+            ("1 > 2", Opcode::GreaterThan),
+        ];
+
+        for (input, opcode) in test_cases {
+            // OpConstant 0, OpConstant 1, the operator, OpPop.
+            let expected = vec![0, 0, 0, 0, 0, 1, opcode as u8, Opcode::Pop as u8];
+            assert_eq!(expected, compile(input).instructions, "input: {}", input);
+            assert_eq!(vec![int(1), int(2)], compile(input).constants, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_prefix_operators() {
+        assert_eq!(vec![0, 0, 0, Opcode::Minus as u8, Opcode::Pop as u8], compile("-1").instructions);
+        assert_eq!(vec![Opcode::True as u8, Opcode::Bang as u8, Opcode::Pop as u8], compile("!true").instructions);
+
+        assert_compiles(
+            "-(1 + 2) * !false",
+            vec![int(1), int(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Minus, &[]),
+                make(Opcode::False, &[]),
+                make(Opcode::Bang, &[]),
+                make(Opcode::Mul, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        assert_compiles(
+            "if (true) { 10 }; 3333;",
+            vec![int(10), int(3333)],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[10]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Jump, &[11]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+
+        assert_compiles(
+            "if (true) { 10 } else { let x = 20; }",
+            vec![int(10), int(20)],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[10]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Jump, &[17]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        assert_compiles(
+            "let one = 1; let two = one; two",
+            vec![int(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+
+        // Rebinding reuses the slot, and a trailing `let` leaves null as the program's value.
+        assert_compiles(
+            "let x = 1; let x = x + 1;",
+            vec![int(1), int(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_collections() {
+        assert_compiles(
+            r#"[1, "two"][0]"#,
+            vec![int(1), Object::Str("two".to_string()), int(0)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Array, &[2]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Index, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+
+        assert_compiles(
+            "{2: 3, 1: 4 * 5}",
+            vec![int(2), int(3), int(1), int(4), int(5)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Constant, &[3]),
+                make(Opcode::Constant, &[4]),
+                make(Opcode::Mul, &[]),
+                make(Opcode::Hash, &[4]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_compiles(
+            "fn() { return 5 + 10 }",
+            vec![
+                int(5),
+                int(10),
+                function(&[], 0, vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+            ],
+            vec![make(Opcode::Closure, &[2]), make(Opcode::Pop, &[])],
+        );
+
+        assert_compiles(
+            "fn() { 1; 2 }",
+            vec![
+                int(1),
+                int(2),
+                function(&[], 0, vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Pop, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+            ],
+            vec![make(Opcode::Closure, &[2]), make(Opcode::Pop, &[])],
+        );
+
+        assert_compiles(
+            "fn() { }",
+            vec![function(&[], 0, vec![make(Opcode::Return, &[])])],
+            vec![make(Opcode::Closure, &[0]), make(Opcode::Pop, &[])],
+        );
+    }
+
+    #[test]
+    fn test_calls_and_locals() {
+        assert_compiles(
+            "let add = fn(a, b) { let c = a + b; c }; add(1, 2);",
+            vec![
+                function(&["a", "b"], 3, vec![
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::GetLocal, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::SetLocal, &[2]),
+                    make(Opcode::GetLocal, &[2]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                int(1),
+                int(2),
+            ],
+            vec![
+                make(Opcode::Closure, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_compiles(
+            "len([]); push([], 1);",
+            vec![int(1)],
+            vec![
+                make(Opcode::GetBuiltin, &[0]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::GetBuiltin, &[5]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Call, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+
+        // A global of the same name shadows the builtin.
+        assert_compiles(
+            "let len = 1; len",
+            vec![int(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        assert_compiles(
+            "fn(a) { fn(b) { a + b } }",
+            vec![
+                closure(&["b"], 1, &[Capture::Local(0)], vec![
+                    make(Opcode::GetFree, &[0]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                function(&["a"], 1, vec![make(Opcode::Closure, &[0]), make(Opcode::ReturnValue, &[])]),
+            ],
+            vec![make(Opcode::Closure, &[1]), make(Opcode::Pop, &[])],
+        );
+
+        // Variables are captured through every function in between, and the name of a function
+        // bound once in another through the closure that refers to it.
+        assert_compiles(
+            "fn() { let f = fn(a) { fn(b) { fn(c) { [a, b, c, f] } } }; }",
+            vec![
+                closure(&["c"], 1, &[Capture::Free(0), Capture::Local(0), Capture::Free(1)], vec![
+                    make(Opcode::GetFree, &[0]),
+                    make(Opcode::GetFree, &[1]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::GetFree, &[2]),
+                    make(Opcode::Array, &[4]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                closure(&["b"], 1, &[Capture::Local(0), Capture::Function], vec![
+                    make(Opcode::Closure, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                function(&["a"], 1, vec![make(Opcode::Closure, &[1]), make(Opcode::ReturnValue, &[])]),
+                function(&[], 1, vec![make(Opcode::Closure, &[2]), make(Opcode::SetLocal, &[0]), make(Opcode::Return, &[])]),
+            ],
+            vec![make(Opcode::Closure, &[3]), make(Opcode::Pop, &[])],
+        );
+
+        // A global can be bound again, so a function refers to its own name through it.
+        assert_compiles(
+            "let f = fn() { f };",
+            vec![function(&[], 0, vec![make(Opcode::GetGlobal, &[0]), make(Opcode::ReturnValue, &[])])],
+            vec![
+                make(Opcode::Closure, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        assert_compiles(
+            "let wrapper = fn() { let countDown = fn(x) { countDown(x - 1); }; countDown(1); };",
+            vec![
+                int(1),
+                function(&["x"], 1, vec![
+                    make(Opcode::CurrentClosure, &[]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Sub, &[]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                int(1),
+                function(&[], 1, vec![
+                    make(Opcode::Closure, &[1]),
+                    make(Opcode::SetLocal, &[0]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+            ],
+            vec![
+                make(Opcode::Closure, &[3]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_source_map() {
+        let bytecode = compile("let x = 1;\nx + -true");
+
+        // `-true` fails in OpMinus and `x + ...` in OpAdd, each with the span of its expression.
+        assert_eq!(
+            [
+                (0, Span::new(8, 9, 1, 9)),
+                (3, Span::new(0, 10, 1, 1)),
+                (6, Span::new(11, 12, 2, 1)),
+                (9, Span::new(16, 20, 2, 6)),
+                (10, Span::new(15, 20, 2, 5)),
+                (11, Span::new(11, 20, 2, 1)),
+            ],
+            bytecode.source_map.entries()
+        );
+    }
+
+    #[test]
+    fn test_late_globals() {
+        let input = "let f = fn() { g() }; let g = fn() { y }; f()";
+
+        assert_compiles(
+            input,
+            vec![
+                function(&[], 0, vec![make(Opcode::GetGlobal, &[0]), make(Opcode::Call, &[0]), make(Opcode::ReturnValue, &[])]),
+                function(&[], 0, vec![make(Opcode::GetGlobal, &[2]), make(Opcode::ReturnValue, &[])]),
+            ],
+            vec![
+                make(Opcode::Closure, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::Closure, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::Call, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        // `g` is referenced, and so given a slot, before `f` is bound.
+        assert_eq!(vec!["g", "f", "y"], compile(input).globals);
+    }
+
+    #[test]
+    fn test_late_locals() {
+        // The function reads the global `x` until its own `x` is bound, but the closure, which
+        // can only run later, captures the local.
+        assert_compiles(
+            "fn() { let y = x; let h = fn() { x }; let x = 1; h() }",
+            vec![
+                closure(&[], 0, &[Capture::Local(2)], vec![make(Opcode::GetFree, &[0]), make(Opcode::ReturnValue, &[])]),
+                int(1),
+                function(&[], 3, vec![
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::SetLocal, &[0]),
+                    make(Opcode::Closure, &[0]),
+                    make(Opcode::SetLocal, &[1]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::SetLocal, &[2]),
+                    make(Opcode::GetLocal, &[1]),
+                    make(Opcode::Call, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+            ],
+            vec![make(Opcode::Closure, &[2]), make(Opcode::Pop, &[])],
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use crate::eval::builtins::BUILTINS;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    Builtin,
+    /// A local of an enclosing function, captured when the closure is created.
+    Free,
+    /// The function being defined, as seen from its own body.
+    Function,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Maps names to the slots they are stored in. Each function body gets its own table enclosing
+/// the one it was defined in; the outermost table holds the globals and builtins.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    /// Locals that a `let` later in the function binds, see `declare`.
+    declared: HashMap<String, Symbol>,
+    num_definitions: usize,
+    free_symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// An outermost table with every builtin defined, as programs are compiled against.
+    pub fn with_builtins() -> SymbolTable {
+        let mut table = SymbolTable::new();
+        for (index, builtin) in BUILTINS.iter().enumerate() {
+            table.define_builtin(index, builtin.name);
+        }
+        table
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable { outer: Some(Box::new(outer)), ..SymbolTable::default() }
+    }
+
+    /// Gives back the enclosing table, or `None` for the outermost one.
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        self.outer.map(|outer| *outer)
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.outer.is_none()
+    }
+
+    /// Number of global or local slots the table has handed out.
+    pub fn num_definitions(&self) -> usize {
+        self.num_definitions
+    }
+
+    /// Symbols of enclosing functions referenced from this one, in the order their values are
+    /// captured.
+    pub fn free_symbols(&self) -> &[Symbol] {
+        &self.free_symbols
+    }
+
+    /// Binds `name` in this scope. Binding a name again in the same scope reuses its slot, so
+    /// `let x = x + 1;` reads and then overwrites the old value, as the evaluator does.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.store.get(name) {
+            if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) {
+                return symbol.clone();
+            }
+        }
+        if let Some(symbol) = self.declared.remove(name) {
+            self.store.insert(name.to_string(), symbol.clone());
+            return symbol;
+        }
+
+        let scope = if self.is_global() { SymbolScope::Global } else { SymbolScope::Local };
+        let symbol = Symbol { name: name.to_string(), scope, index: self.num_definitions };
+        self.num_definitions += 1;
+
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    /// Sets aside a local slot for `name`, which a `let` further on in the function binds. Until
+    /// then the function itself still resolves the name in the enclosing scopes, as the evaluator
+    /// does, but functions nested in it already see the local, since they may only run once it
+    /// is bound.
+    pub fn declare(&mut self, name: &str) {
+        if self.store.get(name).is_some_and(|symbol| symbol.scope == SymbolScope::Local) || self.declared.contains_key(name) {
+            return;
+        }
+
+        let symbol = Symbol { name: name.to_string(), scope: SymbolScope::Local, index: self.num_definitions };
+        self.num_definitions += 1;
+        self.declared.insert(name.to_string(), symbol);
+    }
+
+    /// Binds `name` in the outermost table, for a name used before anything binds it: the VM
+    /// reports reading the slot if nothing has been stored there by then.
+    pub fn define_global(&mut self, name: &str) -> Symbol {
+        match &mut self.outer {
+            Some(outer) => outer.define_global(name),
+            None => self.define(name),
+        }
+    }
+
+    /// Names of the global slots, by index.
+    pub fn global_names(&self) -> Vec<String> {
+        if let Some(outer) = &self.outer {
+            return outer.global_names();
+        }
+
+        let mut names = vec![String::new(); self.num_definitions];
+        for symbol in self.store.values().filter(|symbol| symbol.scope == SymbolScope::Global) {
+            names[symbol.index] = symbol.name.clone();
+        }
+        names
+    }
+
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let symbol = Symbol { name: name.to_string(), scope: SymbolScope::Builtin, index };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol { name: name.to_string(), scope: SymbolScope::Function, index: 0 };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol { name: original.name.clone(), scope: SymbolScope::Free, index: self.free_symbols.len() };
+        self.free_symbols.push(original);
+
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+
+    /// Looks `name` up through the enclosing tables. Locals of an enclosing function are turned
+    /// into free symbols of every function in between, so each closure captures them in turn.
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve_enclosing(name)?;
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            _ => Some(self.define_free(symbol)),
+        }
+    }
+
+    /// Like `resolve`, for a function nested in this one, which also sees the declared locals.
+    fn resolve_enclosing(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.declared.get(name) {
+            return Some(symbol.clone());
+        }
+
+        self.resolve(name)
+    }
+}
+
+#[cfg(test)]
+mod symbol_table_tests {
+    use crate::compiler::symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol { name: name.to_string(), scope, index }
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(symbol("a", SymbolScope::Global, 0), global.define("a"));
+        assert_eq!(symbol("b", SymbolScope::Global, 1), global.define("b"));
+        assert_eq!(symbol("a", SymbolScope::Global, 0), global.define("a"));
+        global.define_builtin(3, "len");
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(symbol("c", SymbolScope::Local, 0), local.define("c"));
+        assert_eq!(symbol("b", SymbolScope::Local, 1), local.define("b"));
+
+        assert_eq!(Some(symbol("a", SymbolScope::Global, 0)), local.resolve("a"));
+        assert_eq!(Some(symbol("b", SymbolScope::Local, 1)), local.resolve("b"));
+        assert_eq!(Some(symbol("len", SymbolScope::Builtin, 3)), local.resolve("len"));
+        assert_eq!(None, local.resolve("d"));
+        assert!(local.free_symbols().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define("b");
+
+        let mut second = SymbolTable::new_enclosed(first);
+        second.define("c");
+
+        assert_eq!(Some(symbol("a", SymbolScope::Global, 0)), second.resolve("a"));
+        assert_eq!(Some(symbol("b", SymbolScope::Free, 0)), second.resolve("b"));
+        assert_eq!(Some(symbol("c", SymbolScope::Local, 0)), second.resolve("c"));
+        assert_eq!([symbol("b", SymbolScope::Local, 0)], second.free_symbols());
+
+        // A local defined after a free reference gets a slot of its own.
+        assert_eq!(symbol("b", SymbolScope::Local, 1), second.define("b"));
+        assert_eq!(Some(symbol("b", SymbolScope::Local, 1)), second.resolve("b"));
+    }
+
+    #[test]
+    fn test_declare() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut outer = SymbolTable::new_enclosed(global);
+        outer.define("p");
+        outer.declare("p");
+        outer.declare("a");
+
+        // Before its `let`, the function itself still sees the global, but a nested one the local.
+        assert_eq!(Some(symbol("a", SymbolScope::Global, 0)), outer.resolve("a"));
+        let mut inner = SymbolTable::new_enclosed(outer);
+        assert_eq!(Some(symbol("a", SymbolScope::Free, 0)), inner.resolve("a"));
+        assert_eq!([symbol("a", SymbolScope::Local, 1)], inner.free_symbols());
+
+        let mut outer = inner.into_outer().unwrap();
+        assert_eq!(symbol("a", SymbolScope::Local, 1), outer.define("a"));
+        assert_eq!(Some(symbol("a", SymbolScope::Local, 1)), outer.resolve("a"));
+        assert_eq!(2, outer.num_definitions());
+    }
+
+    #[test]
+    fn test_function_name() {
+        let mut global = SymbolTable::new();
+        global.define("f");
+
+        let mut local = SymbolTable::new_enclosed(global);
+        local.define_function_name("f");
+        assert_eq!(Some(symbol("f", SymbolScope::Function, 0)), local.resolve("f"));
+
+        // A parameter of the same name shadows the function.
+        assert_eq!(symbol("f", SymbolScope::Local, 0), local.define("f"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::rc::Rc;
+
+use crate::cst::SyntaxKind;
+use crate::token::Token;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+}
+
+/// Source text that carries no meaning for the grammar but must survive a round trip.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+impl Trivia {
+    pub fn new(kind: TriviaKind, text: &str) -> Trivia {
+        Trivia { kind, text: text.to_string() }
+    }
+}
+
+/// A token with its source text and surrounding trivia. Trailing trivia runs up to, but not
+/// including, the next newline; everything after that leads the next token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GreenToken {
+    pub token: Token,
+    pub text: String,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+impl GreenToken {
+    /// Length in bytes of the token and its trivia.
+    pub fn width(&self) -> usize {
+        trivia_width(&self.leading) + self.text.len() + trivia_width(&self.trailing)
+    }
+
+    pub fn leading_width(&self) -> usize {
+        trivia_width(&self.leading)
+    }
+
+    fn write_to(&self, out: &mut String) {
+        self.leading.iter().for_each(|trivia| out.push_str(&trivia.text));
+        out.push_str(&self.text);
+        self.trailing.iter().for_each(|trivia| out.push_str(&trivia.text));
+    }
+}
+
+fn trivia_width(trivia: &[Trivia]) -> usize {
+    trivia.iter().map(|trivia| trivia.text.len()).sum()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width(),
+            GreenElement::Token(token) => token.width(),
+        }
+    }
+
+    fn write_to(&self, out: &mut String) {
+        match self {
+            GreenElement::Node(node) => node.write_to(out),
+            GreenElement::Token(token) => token.write_to(out),
+        }
+    }
+}
+
+/// An immutable syntax tree node. Green nodes know their width but not their position, so
+/// identical subtrees can be shared; `SyntaxNode` adds positions and parent links on top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    width: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        let width = children.iter().map(|child| child.width()).sum();
+        GreenNode { kind, width, children }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// The exact source text the node was built from.
+    pub fn text(&self) -> String {
+        let mut out = String::with_capacity(self.width);
+        self.write_to(&mut out);
+        out
+    }
+
+    fn write_to(&self, out: &mut String) {
+        self.children.iter().for_each(|child| child.write_to(out));
+    }
+}
+
+/// Builds a green tree bottom up. Nodes are opened with `start_node` (or `start_node_at` a
+/// `checkpoint`, to wrap children added earlier, like the left operand of an infix expression)
+/// and closed with `finish_node`.
+#[derive(Debug, Default)]
+pub struct GreenBuilder {
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenBuilder {
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    pub fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        self.parents.push((kind, checkpoint));
+    }
+
+    pub fn token(&mut self, token: Rc<GreenToken>) {
+        self.children.push(GreenElement::Token(token));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, first) = self.parents.pop().expect("finish_node without start_node");
+        let children = self.children.split_off(first);
+        self.children.push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Number of nodes started but not yet finished.
+    pub fn depth(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn finish(mut self) -> Rc<GreenNode> {
+        assert!(self.parents.is_empty() && self.children.len() == 1, "unbalanced green tree");
+        match self.children.pop() {
+            Some(GreenElement::Node(node)) => node,
+            _ => unreachable!("the root of a green tree is a node"),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use crate::cst::red::{SyntaxNode, SyntaxToken};
+use crate::cst::SyntaxKind;
+use crate::parser::ast::{
+    BlockStatement, Expr, ExprKind, Ident, InfixOperator, Let, PrefixOperator, Program, Stmt, StmtKind,
+};
+use crate::token::{Span, Token};
+
+/// Converts an error-free CST into the typed AST, with the spans `Parser` would have given each
+/// node.
+pub(crate) fn lower(root: &SyntaxNode, source: &str) -> Program {
+    let lowerer = Lowerer { line_starts: line_starts(source) };
+
+    let mut program = Program::new();
+    program.statements = root.children().iter().map(|stmt| lowerer.stmt(stmt)).collect();
+    program
+}
+
+fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+struct Lowerer {
+    line_starts: Vec<usize>,
+}
+
+impl Lowerer {
+    fn span(&self, range: Range<usize>) -> Span {
+        let line = self.line_starts.partition_point(|&start| start <= range.start);
+        let col = range.start - self.line_starts[line - 1] + 1;
+        Span::new(range.start, range.end, line, col)
+    }
+
+    fn stmt(&self, node: &SyntaxNode) -> Stmt {
+        let span = self.span(node.text_range());
+
+        let kind = match node.kind() {
+            SyntaxKind::LetStmt => {
+                StmtKind::Let(Let { ident: self.ident(&nth_token(node, 1)), expr: self.expr(&nth_child(node, 0)) })
+            }
+            SyntaxKind::ReturnStmt => StmtKind::Return(node.children().first().map(|expr| self.expr(expr))),
+            SyntaxKind::ExprStmt => StmtKind::Expr(self.expr(&nth_child(node, 0))),
+            kind => unreachable!("{:?} is not a statement", kind),
+        };
+
+        Stmt::new(kind, span)
+    }
+
+    fn block(&self, node: &SyntaxNode) -> BlockStatement {
+        let mut block = BlockStatement::new();
+        block.statements = node.children().iter().map(|stmt| self.stmt(stmt)).collect();
+        block
+    }
+
+    fn ident(&self, token: &SyntaxToken) -> Ident {
+        match token.token() {
+            Token::Ident(name) => Ident::new(name, self.span(token.text_range())),
+            other => unreachable!("expected an identifier, found {:?}", other),
+        }
+    }
+
+    fn expr(&self, node: &SyntaxNode) -> Expr {
+        let span = self.span(node.text_range());
+        let children = node.children();
+
+        let kind = match node.kind() {
+            SyntaxKind::NameRef => ExprKind::Ident(self.ident(&nth_token(node, 0))),
+            SyntaxKind::Literal => match nth_token(node, 0).token() {
+                Token::Int(value) => ExprKind::IntLiteral(*value),
+                Token::Str(value) => ExprKind::StringLiteral(value.clone()),
+                Token::True => ExprKind::Boolean(true),
+                Token::False => ExprKind::Boolean(false),
+                other => unreachable!("{:?} is not a literal", other),
+            },
+            SyntaxKind::PrefixExpr => {
+                let operator = match nth_token(node, 0).token() {
+                    Token::Bang => PrefixOperator::Not,
+                    Token::Minus => PrefixOperator::Negate,
+                    other => unreachable!("{:?} is not a prefix operator", other),
+                };
+                ExprKind::PrefixExpr { expr: Box::new(self.expr(&children[0])), operator: Some(operator) }
+            }
+            SyntaxKind::InfixExpr => {
+                let operator = match nth_token(node, 0).token() {
+                    Token::Plus => InfixOperator::Add,
+                    Token::Minus => InfixOperator::Sub,
+                    Token::Asterisk => InfixOperator::Mul,
+                    Token::Slash => InfixOperator::Div,
+                    Token::Eq => InfixOperator::Equals,
+                    Token::NotEq => InfixOperator::NotEquals,
+                    Token::LT => InfixOperator::LessThan,
+                    Token::GT => InfixOperator::GreaterThan,
+                    other => unreachable!("{:?} is not an infix operator", other),
+                };
+                ExprKind::InfixExpr {
+                    left: Box::new(self.expr(&children[0])),
+                    right: Box::new(self.expr(&children[1])),
+                    operator,
+                }
+            }
+            // The AST has no node for parentheses: the grouped expression takes their span.
+            SyntaxKind::ParenExpr => self.expr(&children[0]).kind,
+            SyntaxKind::IfExpr => ExprKind::If {
+                condition: Box::new(self.expr(&children[0])),
+                consequence: self.block(&children[1]),
+                alternative: children.get(2).map(|block| self.block(block)),
+            },
+            SyntaxKind::FnExpr => ExprKind::Function {
+                params: children[0]
+                    .tokens()
+                    .iter()
+                    .filter(|token| matches!(token.token(), Token::Ident(_)))
+                    .map(|token| self.ident(token))
+                    .collect(),
+                body: self.block(&children[1]),
+            },
+            SyntaxKind::CallExpr => ExprKind::Call {
+                callee: Box::new(self.expr(&children[0])),
+                args: children[1].children().iter().map(|arg| self.expr(arg)).collect(),
+            },
+            SyntaxKind::ArrayExpr => ExprKind::Array(children.iter().map(|element| self.expr(element)).collect()),
+            SyntaxKind::IndexExpr => ExprKind::Index {
+                target: Box::new(self.expr(&children[0])),
+                index: Box::new(self.expr(&children[1])),
+            },
+            SyntaxKind::HashExpr => ExprKind::Hash(
+                children
+                    .iter()
+                    .map(|pair| {
+                        let pair = pair.children();
+                        (self.expr(&pair[0]), self.expr(&pair[1]))
+                    })
+                    .collect(),
+            ),
+            kind => unreachable!("{:?} is not an expression", kind),
+        };
+
+        Expr::new(kind, span)
+    }
+}
+
+fn nth_child(node: &SyntaxNode, n: usize) -> SyntaxNode {
+    node.children().swap_remove(n)
+}
+
+fn nth_token(node: &SyntaxNode, n: usize) -> SyntaxToken {
+    node.tokens().swap_remove(n)
+}
@@ -0,0 +1,205 @@
+//! A lossless concrete syntax tree. Every byte of the input, whitespace and comments included,
+//! belongs to exactly one token, so printing the tree gives back the source unchanged. Tooling
+//! that needs the exact text works on the CST; the evaluator keeps using `ast::Program`, which
+//! `Cst::to_program` produces.
+//!
+//! The tree has two layers, as in Roslyn and rust-analyzer: immutable, position-free green nodes
+//! (`green`) and red `SyntaxNode`s built on demand over them with offsets and parent links (`red`).
+
+pub mod green;
+pub mod red;
+
+mod lower;
+mod parser;
+
+use crate::parser::ast::Program;
+use crate::parser::ParseError;
+use red::SyntaxNode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Program,
+    LetStmt,
+    ReturnStmt,
+    ExprStmt,
+    /// `{ ... }` after `if`, `else` or a parameter list.
+    Block,
+
+    /// An identifier used as an expression.
+    NameRef,
+    Literal,
+    PrefixExpr,
+    InfixExpr,
+    ParenExpr,
+    IfExpr,
+    FnExpr,
+    ParamList,
+    CallExpr,
+    ArgList,
+    ArrayExpr,
+    IndexExpr,
+    HashExpr,
+    HashPair,
+
+    /// Tokens skipped while recovering from a parse error.
+    Error,
+}
+
+pub struct Cst {
+    source: String,
+    root: SyntaxNode,
+    errors: Vec<ParseError>,
+}
+
+impl Cst {
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// The same errors `Parser::parse_program` reports for the source.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Converts the tree to the typed AST, failing if the source did not parse.
+    pub fn to_program(&self) -> Result<Program, Vec<ParseError>> {
+        if self.errors.is_empty() {
+            Ok(lower::lower(&self.root, &self.source))
+        } else {
+            Err(self.errors.clone())
+        }
+    }
+}
+
+/// Parses `source` into a CST. This never fails: parse errors are collected in `Cst::errors` and
+/// the tokens around them are kept in `SyntaxKind::Error` nodes.
+pub fn parse(source: &str) -> Cst {
+    let (green, errors) = parser::parse(source);
+
+    Cst { source: source.to_string(), root: SyntaxNode::new_root(green), errors }
+}
+
+#[cfg(test)]
+mod cst_tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::cst::green::{Trivia, TriviaKind};
+    use crate::cst::{parse, SyntaxKind};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::token::Token;
+
+    const VALID: &[&str] = &[
+        "let x = 5;\nlet y = (x + 2) * -x;\nreturn;",
+        "  // leading\nlet add = fn(a, b) { return a + b; }; /* trailing */\nadd(1, 2)\n\n",
+        "if (x < 10) { puts(\"small\") } else { let z = [1, 2][0]; z }",
+        "{\"one\": 1, true: fn() {}, 3: [ ] }[\"one\"]\r\n!!true == false",
+        "let f = fn(x) { fn(y) { x(y) } }; f(fn(n) { n })(3)",
+        "",
+    ];
+
+    const INVALID: &[&str] = &[
+        "let = 5;\nlet y = 1;",
+        "let x 5; let z = 2",
+        "1 + ; }\n{ 1: }",
+        "if (x { 1 };\nlet ok = 1;",
+        "fn(a, 1) { a }; [1, 2; \"oops",
+        "let s = \"unterminated",
+        "let c = 1 /* open",
+        "é + @",
+        "fn() { let x = ; x",
+        "( \"s\" * { } }",
+        "( ! { } :",
+        "let f = fn() { [{}; 1 }; f(",
+        "a\0b",
+        "let x = 1;\0// c",
+    ];
+
+    #[test]
+    fn test_round_trips_source() {
+        for source in VALID.iter().chain(INVALID) {
+            assert_eq!(*source, parse(source).root().to_string(), "source: {:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_to_program_matches_parser() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join("fmt");
+        let files: Vec<String> = fs::read_dir(dir).unwrap().map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap()).collect();
+
+        for source in VALID.iter().copied().chain(files.iter().map(String::as_str)) {
+            let expected = Parser::new(Lexer::new(source)).parse_program().unwrap();
+            let program = parse(source).to_program().unwrap();
+
+            // Debug output includes spans, which `PartialEq` on the AST ignores.
+            assert_eq!(format!("{:?}", expected), format!("{:?}", program), "source: {:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_errors_match_parser() {
+        for source in INVALID {
+            let expected = Parser::new(Lexer::new(source)).parse_program().unwrap_err();
+            let cst = parse(source);
+
+            assert_eq!(expected, cst.errors(), "source: {:?}", source);
+            assert_eq!(Err(expected), cst.to_program(), "source: {:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_trivia_attachment() {
+        let cst = parse("// doc\nlet x = 1; // one\n  x");
+        let tokens = cst.root().descendant_tokens();
+
+        let whitespace = |text: &str| Trivia::new(TriviaKind::Whitespace, text);
+        let comment = |text: &str| Trivia::new(TriviaKind::Comment, text);
+
+        assert_eq!(&Token::Let, tokens[0].token());
+        assert_eq!([comment("// doc"), whitespace("\n")], tokens[0].leading_trivia());
+        assert_eq!([whitespace(" ")], tokens[0].trailing_trivia());
+
+        assert_eq!(&Token::Semicolon, tokens[4].token());
+        assert_eq!([whitespace(" "), comment("// one")], tokens[4].trailing_trivia());
+
+        assert_eq!("x", tokens[5].text());
+        assert_eq!([whitespace("\n  ")], tokens[5].leading_trivia());
+        assert_eq!(27..28, tokens[5].text_range());
+        assert_eq!(&Token::Eof, tokens[6].token());
+    }
+
+    #[test]
+    fn test_red_tree_navigation() {
+        let cst = parse("1 + 2 * 3;");
+        let root = cst.root();
+
+        assert_eq!(SyntaxKind::Program, root.kind());
+        let stmt = &root.children()[0];
+        assert_eq!(SyntaxKind::ExprStmt, stmt.kind());
+
+        let sum = &stmt.children()[0];
+        assert_eq!(SyntaxKind::InfixExpr, sum.kind());
+        assert_eq!(0..9, sum.text_range());
+
+        let product = &sum.children()[1];
+        assert_eq!(SyntaxKind::InfixExpr, product.kind());
+        assert_eq!(4..9, product.range());
+        assert_eq!("2 * 3", product.to_string());
+        assert_eq!(SyntaxKind::InfixExpr, product.parent().unwrap().kind());
+        assert_eq!(&Token::Asterisk, product.tokens()[0].token());
+    }
+
+    #[test]
+    fn test_error_nodes_keep_skipped_tokens() {
+        let cst = parse("let = 5; 1");
+        let root = cst.root();
+
+        let kinds: Vec<SyntaxKind> = root.children().iter().map(|node| node.kind()).collect();
+        assert_eq!(vec![SyntaxKind::LetStmt, SyntaxKind::ExprStmt], kinds);
+
+        let error = &root.children()[0].children()[0];
+        assert_eq!(SyntaxKind::Error, error.kind());
+        assert_eq!("= 5; ", error.to_string());
+    }
+}
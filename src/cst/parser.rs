@@ -0,0 +1,455 @@
+use std::rc::Rc;
+
+use crate::cst::green::{GreenBuilder, GreenNode, GreenToken, Trivia, TriviaKind};
+use crate::cst::SyntaxKind;
+use crate::lexer::Lexer;
+use crate::parser::ast::ExprPrecedence;
+use crate::parser::{precedence, ParseError};
+use crate::token::{Span, Token};
+
+/// Parses `source` into a green tree. The grammar, the errors and the recovery after an error
+/// mirror `Parser`, but every token is kept: tokens skipped while recovering end up in
+/// `SyntaxKind::Error` nodes, so the tree always covers the whole input.
+pub(crate) fn parse(source: &str) -> (Rc<GreenNode>, Vec<ParseError>) {
+    let mut parser = CstParser { tokens: lex(source), pos: 0, builder: GreenBuilder::default(), errors: vec![] };
+    parser.program();
+
+    (parser.builder.finish(), parser.errors)
+}
+
+/// Lexes `source` into tokens that carry their trivia. The gaps the lexer skips are whitespace,
+/// and comments come through as trivia tokens.
+fn lex(source: &str) -> Vec<(Rc<GreenToken>, Span)> {
+    let mut lexer = Lexer::new(source).with_comments();
+    let mut tokens: Vec<(GreenToken, Span)> = vec![];
+    let mut pending: Vec<Trivia> = vec![];
+    let mut end = 0;
+
+    loop {
+        let tok = lexer.next_token();
+        if tok.span.start > end {
+            pending.push(Trivia::new(TriviaKind::Whitespace, &source[end..tok.span.start]));
+        }
+        end = tok.span.end;
+
+        match tok.token {
+            Token::Comment(text) => pending.push(Trivia::new(TriviaKind::Comment, &text)),
+            token => {
+                let leading = match tokens.last_mut() {
+                    Some((previous, _)) => split_trailing(&mut previous.trailing, std::mem::take(&mut pending)),
+                    None => std::mem::take(&mut pending),
+                };
+
+                let is_eof = token == Token::Eof;
+                let text = source[tok.span.start..tok.span.end].to_string();
+                tokens.push((GreenToken { token, text, leading, trailing: vec![] }, tok.span));
+
+                if is_eof {
+                    return tokens.into_iter().map(|(token, span)| (Rc::new(token), span)).collect();
+                }
+            }
+        }
+    }
+}
+
+/// Moves the trivia up to the first newline into `trailing` and returns the rest, which leads
+/// the next token.
+fn split_trailing(trailing: &mut Vec<Trivia>, trivia: Vec<Trivia>) -> Vec<Trivia> {
+    let mut leading = vec![];
+
+    for piece in trivia {
+        if !leading.is_empty() {
+            leading.push(piece);
+            continue;
+        }
+
+        match piece.text.find('\n') {
+            Some(newline) if piece.kind == TriviaKind::Whitespace => {
+                if newline > 0 {
+                    trailing.push(Trivia::new(TriviaKind::Whitespace, &piece.text[..newline]));
+                }
+                leading.push(Trivia::new(TriviaKind::Whitespace, &piece.text[newline..]));
+            }
+            _ => trailing.push(piece),
+        }
+    }
+
+    leading
+}
+
+/// Where recovering from an error stopped skipping tokens.
+#[derive(PartialEq)]
+enum Stop {
+    /// Where a statement can start.
+    Statement,
+    /// At a `}`, which may close the enclosing block.
+    RBrace,
+    /// Just after a `}` that `Parser` takes to close the enclosing block.
+    ClosedBlock,
+}
+
+struct CstParser {
+    tokens: Vec<(Rc<GreenToken>, Span)>,
+    pos: usize,
+    builder: GreenBuilder,
+    errors: Vec<ParseError>,
+}
+
+impl CstParser {
+    fn current(&self) -> &Token {
+        &self.tokens[self.pos].0.token
+    }
+
+    fn current_span(&self) -> Span {
+        self.tokens[self.pos].1
+    }
+
+    fn at(&self, token: &Token) -> bool {
+        self.current() == token
+    }
+
+    /// Adds the current token to the open node and moves past it. The cursor stays on `Eof`.
+    fn bump(&mut self) {
+        self.builder.token(Rc::clone(&self.tokens[self.pos].0));
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.at(&token) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(ParseError::UnexpectedToken { expected: vec![token], found: self.current().clone(), span: self.current_span() })
+        }
+    }
+
+    fn expect_list_end(&mut self, end: Token) -> Result<(), ParseError> {
+        if self.at(&end) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Comma, end],
+                found: self.current().clone(),
+                span: self.current_span(),
+            })
+        }
+    }
+
+    fn ident(&mut self) -> Result<(), ParseError> {
+        match self.current() {
+            Token::Ident(_) => {
+                self.bump();
+                Ok(())
+            }
+            other => Err(ParseError::ExpectedIdent { found: other.clone(), span: self.current_span() }),
+        }
+    }
+
+    fn program(&mut self) {
+        self.builder.start_node(SyntaxKind::Program);
+
+        while !self.at(&Token::Eof) {
+            self.stmt(true);
+        }
+
+        // The end of input carries the trivia after the last statement.
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    /// Parses one statement, recovering from an error by skipping to the next point where a
+    /// statement can start. Returns whether the recovery ended the enclosing block.
+    fn stmt(&mut self, top_level: bool) -> bool {
+        let depth = self.builder.depth();
+
+        let result = match self.current() {
+            Token::Let => self.let_stmt(),
+            Token::Return => self.return_stmt(),
+            _ => self.expr_stmt(),
+        };
+
+        let Err(e) = result else {
+            return false;
+        };
+
+        self.builder.start_node(SyntaxKind::Error);
+        let stop = self.synchronize(&e);
+
+        // Unlike a block, the program has no use for a stray `}`.
+        if stop == Stop::RBrace && top_level {
+            self.bump();
+        }
+
+        self.errors.push(e);
+        while self.builder.depth() > depth {
+            self.builder.finish_node();
+        }
+
+        stop == Stop::ClosedBlock && !top_level
+    }
+
+    /// Skips tokens like `Parser::synchronize`: past a `;`, up to a `}` closing the enclosing
+    /// block, up to the end of input, or up to a `let`/`return`.
+    fn synchronize(&mut self, error: &ParseError) -> Stop {
+        let mut depth = 0;
+
+        // `Parser` finds unexpected tokens by looking ahead, so it starts skipping from the token
+        // before them, which this parser has already consumed.
+        if matches!(error, ParseError::UnexpectedToken { .. }) {
+            match &self.tokens[self.pos - 1].0.token {
+                Token::RBrace => return Stop::ClosedBlock,
+                Token::Semicolon => return Stop::Statement,
+                Token::LBrace => depth += 1,
+                _ => {}
+            }
+
+            if depth == 0 && matches!(self.current(), Token::Let | Token::Return) {
+                return Stop::Statement;
+            }
+        }
+
+        loop {
+            match self.current() {
+                Token::Eof => return Stop::Statement,
+                Token::LBrace => depth += 1,
+                Token::RBrace if depth == 0 => return Stop::RBrace,
+                Token::RBrace => depth -= 1,
+                Token::Semicolon if depth == 0 => {
+                    self.bump();
+                    return Stop::Statement;
+                }
+                _ => {}
+            }
+
+            self.bump();
+
+            if depth == 0 && matches!(self.current(), Token::Let | Token::Return) {
+                return Stop::Statement;
+            }
+        }
+    }
+
+    fn let_stmt(&mut self) -> Result<(), ParseError> {
+        self.builder.start_node(SyntaxKind::LetStmt);
+        self.bump();
+        self.ident()?;
+        self.expect(Token::Assign)?;
+        self.expr(ExprPrecedence::LOW)?;
+        self.skip_semicolon();
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn return_stmt(&mut self) -> Result<(), ParseError> {
+        self.builder.start_node(SyntaxKind::ReturnStmt);
+        self.bump();
+        if !self.at(&Token::Semicolon) && !self.at(&Token::Eof) {
+            self.expr(ExprPrecedence::LOW)?;
+        }
+        self.skip_semicolon();
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn expr_stmt(&mut self) -> Result<(), ParseError> {
+        self.builder.start_node(SyntaxKind::ExprStmt);
+        self.expr(ExprPrecedence::LOW)?;
+        self.skip_semicolon();
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn skip_semicolon(&mut self) {
+        if self.at(&Token::Semicolon) {
+            self.bump();
+        }
+    }
+
+    fn expr(&mut self, min: ExprPrecedence) -> Result<(), ParseError> {
+        let checkpoint = self.builder.checkpoint();
+        self.prefix_expr()?;
+
+        while !self.at(&Token::Semicolon) && min < precedence(self.current()) {
+            match self.current() {
+                Token::LParen => {
+                    self.builder.start_node_at(checkpoint, SyntaxKind::CallExpr);
+                    self.builder.start_node(SyntaxKind::ArgList);
+                    self.bump();
+                    self.expr_list(Token::RParen)?;
+                    self.builder.finish_node();
+                }
+                Token::LBracket => {
+                    self.builder.start_node_at(checkpoint, SyntaxKind::IndexExpr);
+                    self.bump();
+                    self.expr(ExprPrecedence::LOW)?;
+                    self.expect(Token::RBracket)?;
+                }
+                operator => {
+                    let own = precedence(operator);
+                    self.builder.start_node_at(checkpoint, SyntaxKind::InfixExpr);
+                    self.bump();
+                    self.expr(own)?;
+                }
+            }
+            self.builder.finish_node();
+        }
+
+        Ok(())
+    }
+
+    fn prefix_expr(&mut self) -> Result<(), ParseError> {
+        let kind = match self.current() {
+            Token::Ident(_) => SyntaxKind::NameRef,
+            Token::Int(_) | Token::Str(_) | Token::True | Token::False => SyntaxKind::Literal,
+            Token::Bang | Token::Minus => {
+                self.builder.start_node(SyntaxKind::PrefixExpr);
+                self.bump();
+                self.expr(ExprPrecedence::PREFIX)?;
+                self.builder.finish_node();
+                return Ok(());
+            }
+            Token::LParen => {
+                self.builder.start_node(SyntaxKind::ParenExpr);
+                self.bump();
+                self.expr(ExprPrecedence::LOW)?;
+                self.expect(Token::RParen)?;
+                self.builder.finish_node();
+                return Ok(());
+            }
+            Token::If => return self.if_expr(),
+            Token::Function => return self.fn_expr(),
+            Token::LBracket => {
+                self.builder.start_node(SyntaxKind::ArrayExpr);
+                self.bump();
+                self.expr_list(Token::RBracket)?;
+                self.builder.finish_node();
+                return Ok(());
+            }
+            Token::LBrace => return self.hash_expr(),
+            Token::Error(_) => {
+                return Err(ParseError::InvalidToken { found: self.current().clone(), span: self.current_span() })
+            }
+            other => return Err(ParseError::NoPrefixParseFn { found: other.clone(), span: self.current_span() }),
+        };
+
+        self.builder.start_node(kind);
+        self.bump();
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn if_expr(&mut self) -> Result<(), ParseError> {
+        self.builder.start_node(SyntaxKind::IfExpr);
+        self.bump();
+        self.expect(Token::LParen)?;
+        self.expr(ExprPrecedence::LOW)?;
+        self.expect(Token::RParen)?;
+        self.block()?;
+
+        if self.at(&Token::Else) {
+            self.bump();
+            self.block()?;
+        }
+
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn fn_expr(&mut self) -> Result<(), ParseError> {
+        self.builder.start_node(SyntaxKind::FnExpr);
+        self.bump();
+
+        self.builder.start_node(SyntaxKind::ParamList);
+        self.expect(Token::LParen)?;
+        if self.at(&Token::RParen) {
+            self.bump();
+        } else {
+            self.ident()?;
+            while self.at(&Token::Comma) {
+                self.bump();
+                self.ident()?;
+            }
+            self.expect_list_end(Token::RParen)?;
+        }
+        self.builder.finish_node();
+
+        self.block()?;
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<(), ParseError> {
+        if !self.at(&Token::LBrace) {
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![Token::LBrace],
+                found: self.current().clone(),
+                span: self.current_span(),
+            });
+        }
+
+        self.builder.start_node(SyntaxKind::Block);
+        let open = self.current_span();
+        self.bump();
+
+        while !self.at(&Token::RBrace) {
+            if self.at(&Token::Eof) {
+                return Err(ParseError::UnterminatedBlock { open, span: self.current_span() });
+            }
+            // `Parser` takes a `}` it consumed before an error for the end of the block.
+            if self.stmt(false) {
+                self.builder.finish_node();
+                return Ok(());
+            }
+        }
+
+        self.bump();
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    fn hash_expr(&mut self) -> Result<(), ParseError> {
+        self.builder.start_node(SyntaxKind::HashExpr);
+        self.bump();
+
+        while !self.at(&Token::RBrace) {
+            self.builder.start_node(SyntaxKind::HashPair);
+            self.expr(ExprPrecedence::LOW)?;
+            self.expect(Token::Colon)?;
+            self.expr(ExprPrecedence::LOW)?;
+            self.builder.finish_node();
+
+            if self.at(&Token::Comma) {
+                self.bump();
+            } else if !self.at(&Token::RBrace) {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![Token::Comma, Token::RBrace],
+                    found: self.current().clone(),
+                    span: self.current_span(),
+                });
+            }
+        }
+
+        self.bump();
+        self.builder.finish_node();
+        Ok(())
+    }
+
+    /// The elements of a call's arguments or an array literal, after the opening bracket.
+    fn expr_list(&mut self, end: Token) -> Result<(), ParseError> {
+        if self.at(&end) {
+            self.bump();
+            return Ok(());
+        }
+
+        self.expr(ExprPrecedence::LOW)?;
+        while self.at(&Token::Comma) {
+            self.bump();
+            self.expr(ExprPrecedence::LOW)?;
+        }
+
+        self.expect_list_end(end)
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::cst::green::{GreenElement, GreenNode, GreenToken, Trivia};
+use crate::cst::SyntaxKind;
+use crate::token::Token;
+
+/// A view of a green node at a position in the source, with a link to its parent. Red nodes are
+/// created on demand while walking down from the root and are cheap to clone.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData { green, offset: 0, parent: None }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind()
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// Byte range covered by the node, trivia included.
+    pub fn range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.width()
+    }
+
+    /// Byte range covered by the node's tokens, without the leading trivia of the first one and
+    /// the trailing trivia of the last one.
+    pub fn text_range(&self) -> Range<usize> {
+        let tokens = self.descendant_tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.text_range().start..last.text_range().end,
+            _ => self.0.offset..self.0.offset,
+        }
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+
+        self.0
+            .green
+            .children()
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                        green: Rc::clone(green),
+                        offset,
+                        parent: Some(self.clone()),
+                    }))),
+                    GreenElement::Token(green) => {
+                        SyntaxElement::Token(SyntaxToken { green: Rc::clone(green), offset, parent: self.clone() })
+                    }
+                };
+                offset += child.width();
+                element
+            })
+            .collect()
+    }
+
+    /// Child nodes, skipping tokens.
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// Tokens that are direct children, skipping nodes.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Token(token) => Some(token),
+                SyntaxElement::Node(_) => None,
+            })
+            .collect()
+    }
+
+    /// Every token under the node, in source order.
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        tokens
+    }
+}
+
+/// The node's source text, byte for byte.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green.text())
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.kind(), self.range())
+    }
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+impl SyntaxToken {
+    pub fn token(&self) -> &Token {
+        &self.green.token
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        &self.green.leading
+    }
+
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        &self.green.trailing
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    /// Byte range of the token itself, without trivia.
+    pub fn text_range(&self) -> Range<usize> {
+        let start = self.offset + self.green.leading_width();
+        start..start + self.green.text.len()
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.token(), self.text_range())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
@@ -0,0 +1,222 @@
+use std::fmt::Write;
+
+use crate::compiler::CompileError;
+use crate::eval::object::RuntimeError;
+use crate::parser::ParseError;
+use crate::token::{Span, Token};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A message about a location in the source, ready to be rendered with `render`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic { message, span, notes: vec![] }
+    }
+
+    pub fn with_note(mut self, note: String) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let diagnostic = Diagnostic::new(error.to_string(), Some(error.span()));
+
+        match error {
+            ParseError::UnexpectedToken { expected, .. } if expected.contains(&Token::Semicolon) => {
+                diagnostic.with_note("statements are separated by `;`".to_string())
+            }
+            ParseError::UnterminatedBlock { open, .. } => {
+                diagnostic.with_note(format!("add a `}}` to close the block opened at {}:{}", open.line, open.col))
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        with_unbound_note(Diagnostic::new(error.message.clone(), error.span))
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic::new(error.message.clone(), Some(error.span))
+    }
+}
+
+/// Both engines find unbound names when the code using them runs; reading one gets a hint.
+fn with_unbound_note(diagnostic: Diagnostic) -> Diagnostic {
+    match diagnostic.message.strip_prefix("identifier not found: ") {
+        Some(name) => {
+            let note = format!("bind it first with `let {} = ...;`", name);
+            diagnostic.with_note(note)
+        }
+        None => diagnostic,
+    }
+}
+
+/// Renders `diagnostic` against `source` in the style of rustc: the message, the location, the
+/// offending line with a caret underline and any notes. `origin` names the source (a file path or
+/// `<repl>`) and `color` enables ANSI colors for terminals.
+pub fn render(diagnostic: &Diagnostic, source: &str, origin: &str, color: bool) -> String {
+    let paint = |style: &'static str| if color { style } else { "" };
+    let reset = paint(RESET);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}error{}{}: {}{}", paint(RED), reset, paint(BOLD), diagnostic.message, reset);
+
+    // Without the source, as for a program loaded from a compiled file, only the location is shown.
+    let span = match diagnostic.span {
+        Some(span) if !source.is_empty() => span,
+        span => {
+            if let Some(span) = span {
+                let _ = writeln!(out, " {}-->{} {}:{}:{}", paint(BLUE), reset, origin, span.line, span.col);
+            }
+            for note in &diagnostic.notes {
+                let _ = writeln!(out, "  = {}help{}: {}", paint(BOLD), reset, note);
+            }
+            return out;
+        }
+    };
+
+    let line_text = source.split('\n').nth(span.line - 1).unwrap_or("").trim_end_matches('\r');
+    let line_number = span.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    let _ = writeln!(out, "{}{}-->{} {}:{}:{}", gutter, paint(BLUE), reset, origin, span.line, span.col);
+    let _ = writeln!(out, "{} {}|{}", gutter, paint(BLUE), reset);
+    let _ = writeln!(out, "{}{} |{} {}", paint(BLUE), line_number, reset, line_text);
+
+    // Spans count bytes but the terminal shows characters, so padding and carets are measured in
+    // characters of the line. Tabs stay in the padding so the carets line up with the text above.
+    let start = floor_char_boundary(line_text, span.col.saturating_sub(1));
+    let end = floor_char_boundary(line_text, start + span.end.saturating_sub(span.start));
+    let padding: String = line_text[..start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let width = line_text[start..end].chars().count().max(1);
+    let _ = writeln!(out, "{} {}|{} {}{}{}{}", gutter, paint(BLUE), reset, padding, paint(RED), "^".repeat(width), reset);
+
+    for note in &diagnostic.notes {
+        let _ = writeln!(out, "{} {}={} {}help{}: {}", gutter, paint(BLUE), reset, paint(BOLD), reset, note);
+    }
+
+    out
+}
+
+/// The largest char boundary of `text` at or before `index`, clamped to its length.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use crate::diagnostics::{render, Diagnostic};
+    use crate::eval::object::RuntimeError;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::token::Span;
+
+    fn render_parse_errors(input: &str) -> String {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let errors = p.parse_program().expect_err(input);
+        errors.iter().map(|e| render(&Diagnostic::from(e), input, "test.mk", false)).collect()
+    }
+
+    #[test]
+    fn test_render_parse_error() {
+        let expected = r#"error: expected `)`, found `;`
+ --> test.mk:2:11
+  |
+2 | let y = (x;
+  |           ^
+"#;
+
+        assert_eq!(expected, render_parse_errors("let x = 1;\nlet y = (x;"));
+    }
+
+    #[test]
+    fn test_render_underlines_whole_token_and_notes() {
+        let expected = r#"error: unterminated block: `{` opened at 1:11 is never closed
+ --> test.mk:1:14
+  |
+1 | if (true) { 1
+  |              ^
+  = help: add a `}` to close the block opened at 1:11
+error: expected identifier, found `fn`
+ --> test.mk:1:5
+  |
+1 | let fn = 1
+  |     ^^
+"#;
+
+        let mut output = render_parse_errors("if (true) { 1");
+        output.push_str(&render_parse_errors("let fn = 1"));
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_render_runtime_error() {
+        let source = "let a = 1;\n\tlet b = a + c;";
+        let error = RuntimeError { message: "identifier not found: c".to_string(), span: Some(Span::new(24, 25, 2, 14)) };
+
+        let expected = "error: identifier not found: c
+ --> <repl>:2:14
+  |
+2 | \tlet b = a + c;
+  | \t            ^
+  = help: bind it first with `let c = ...;`
+";
+
+        assert_eq!(expected, render(&Diagnostic::from(&error), source, "<repl>", false));
+    }
+
+    #[test]
+    fn test_render_non_ascii_line() {
+        let source = "let s = \"ééééé\"; s + 1\né + 1";
+        let error = RuntimeError { message: "type mismatch: STRING + INTEGER".to_string(), span: Some(Span::new(22, 27, 1, 23)) };
+
+        let expected = "error: type mismatch: STRING + INTEGER
+ --> x.mk:1:23
+  |
+1 | let s = \"ééééé\"; s + 1
+  |                  ^^^^^
+";
+        assert_eq!(expected, render(&Diagnostic::from(&error), source, "x.mk", false));
+
+        let diagnostic = Diagnostic::new("unknown operator: é".to_string(), Some(Span::new(28, 30, 2, 1)));
+        assert!(render(&diagnostic, source, "x.mk", false).ends_with("2 | é + 1\n  | ^\n"));
+    }
+
+    #[test]
+    fn test_render_without_source() {
+        let error = RuntimeError { message: "identifier not found: c".to_string(), span: Some(Span::new(24, 25, 2, 14)) };
+
+        let expected = "error: identifier not found: c\n --> x.mk:2:14\n  = help: bind it first with `let c = ...;`\n";
+        assert_eq!(expected, render(&Diagnostic::from(&error), "", "x.mk", false));
+    }
+
+    #[test]
+    fn test_render_with_color() {
+        let diagnostic = Diagnostic::new("boom".to_string(), None);
+
+        assert_eq!("\x1b[1;31merror\x1b[0m\x1b[1m: boom\x1b[0m\n", render(&diagnostic, "", "<repl>", true));
+    }
+}
@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+
+use crate::eval::object::{Builtin, Object, RuntimeError};
+
+/// Functions available in every program without being bound by `let`. User bindings with the same
+/// name shadow them.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "len", func: len },
+    Builtin { name: "puts", func: puts },
+    Builtin { name: "first", func: first },
+    Builtin { name: "last", func: last },
+    Builtin { name: "rest", func: rest },
+    Builtin { name: "push", func: push },
+];
+
+thread_local! {
+    /// Where `puts` writes while inside `capture_output`; standard output otherwise.
+    static OUTPUT: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with everything `puts` writes collected instead of sent to standard output, and
+/// returns it along with the result of `f`. The REPL uses this to write program output to its own
+/// writer.
+pub fn capture_output<T>(f: impl FnOnce() -> T) -> (T, Vec<u8>) {
+    let outer = OUTPUT.with(|output| output.replace(Some(vec![])));
+    let result = f();
+    let captured = OUTPUT.with(|output| output.replace(outer)).unwrap_or_default();
+    (result, captured)
+}
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name).copied()
+}
+
+fn error(message: String) -> Object {
+    Object::Error(RuntimeError::new(message))
+}
+
+fn check_arity(name: &str, args: &[Object], want: usize) -> Result<(), Object> {
+    if args.len() == want {
+        Ok(())
+    } else {
+        Err(error(format!("wrong number of arguments to `{}`: want={}, got={}", name, want, args.len())))
+    }
+}
+
+fn len(args: Vec<Object>) -> Object {
+    if let Err(e) = check_arity("len", &args, 1) {
+        return e;
+    }
+
+    match &args[0] {
+        Object::Str(value) => Object::Integer(value.chars().count() as i64),
+        Object::Array(elements) => Object::Integer(elements.len() as i64),
+        Object::Hash(pairs) => Object::Integer(pairs.len() as i64),
+        other => error(format!("argument to `len` not supported, got {}", other.type_name())),
+    }
+}
+
+fn puts(args: Vec<Object>) -> Object {
+    let written = OUTPUT.with(|output| match output.borrow_mut().as_mut() {
+        Some(buffer) => args.iter().try_for_each(|arg| writeln!(buffer, "{}", arg)),
+        None => {
+            let mut stdout = io::stdout().lock();
+            args.iter().try_for_each(|arg| writeln!(stdout, "{}", arg))
+        }
+    });
+
+    match written {
+        Ok(()) => Object::Null,
+        Err(e) => error(format!("`puts` could not write output: {}", e)),
+    }
+}
+
+fn first(args: Vec<Object>) -> Object {
+    if let Err(e) = check_arity("first", &args, 1) {
+        return e;
+    }
+
+    match &args[0] {
+        Object::Array(elements) => elements.first().cloned().unwrap_or(Object::Null),
+        other => error(format!("argument to `first` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+fn last(args: Vec<Object>) -> Object {
+    if let Err(e) = check_arity("last", &args, 1) {
+        return e;
+    }
+
+    match &args[0] {
+        Object::Array(elements) => elements.last().cloned().unwrap_or(Object::Null),
+        other => error(format!("argument to `last` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+fn rest(args: Vec<Object>) -> Object {
+    if let Err(e) = check_arity("rest", &args, 1) {
+        return e;
+    }
+
+    match &args[0] {
+        Object::Array(elements) if elements.is_empty() => Object::Null,
+        Object::Array(elements) => Object::Array(elements[1..].to_vec()),
+        other => error(format!("argument to `rest` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+fn push(args: Vec<Object>) -> Object {
+    if let Err(e) = check_arity("push", &args, 2) {
+        return e;
+    }
+
+    match &args[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.clone();
+            elements.push(args[1].clone());
+            Object::Array(elements)
+        }
+        other => error(format!("argument to `push` must be ARRAY, got {}", other.type_name())),
+    }
+}
@@ -0,0 +1,740 @@
+pub mod builtins;
+pub mod environment;
+pub mod object;
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::eval::environment::{Env, Environment};
+use crate::eval::object::{Function, HashKey, Object, RuntimeError};
+use crate::parser::ast::{BlockStatement, Expr, ExprKind, Ident, InfixOperator, Let, Node, PrefixOperator, Program, Stmt, StmtKind};
+
+/// Deepest nesting of function calls before evaluation fails with a stack overflow. Each call
+/// recurses through the evaluator on the native stack, so this is kept low enough for the default
+/// main thread stack even in a debug build.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+thread_local! {
+    /// Number of function calls being evaluated, so runaway recursion is reported as an error
+    /// instead of overflowing the native stack.
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn eval(node: &Node, env: &Env) -> Object {
+    match node {
+        Node::Program(program) => eval_program(program, env),
+        Node::Stmt(stmt) => eval_stmt(stmt, env),
+        Node::Expr(expr) => eval_expr(expr, env),
+    }
+}
+
+pub fn eval_program(program: &Program, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for stmt in &program.statements {
+        result = eval_stmt(stmt, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            _ => {}
+        }
+    }
+
+    result
+}
+
+fn eval_block_stmt(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for stmt in &block.statements {
+        result = eval_stmt(stmt, env);
+
+        // Return values are passed up unwrapped so that enclosing blocks stop evaluating as well.
+        if result.is_return_or_error() {
+            return result;
+        }
+    }
+
+    result
+}
+
+fn eval_stmt(stmt: &Stmt, env: &Env) -> Object {
+    match &stmt.kind {
+        StmtKind::Let(let_stmt) => eval_let_stmt(let_stmt, env),
+        StmtKind::Return(expr) => {
+            let value = match expr {
+                Some(expr) => eval_expr(expr, env),
+                None => Object::Null,
+            };
+
+            if value.is_return_or_error() {
+                return value;
+            }
+
+            Object::ReturnValue(Box::new(value))
+        }
+        StmtKind::Expr(expr) => eval_expr(expr, env),
+    }
+}
+
+fn eval_let_stmt(let_stmt: &Let, env: &Env) -> Object {
+    let value = eval_expr(&let_stmt.expr, env);
+
+    if value.is_return_or_error() {
+        return value;
+    }
+
+    env.borrow_mut().set(&let_stmt.ident.name, value);
+
+    Object::Null
+}
+
+fn eval_expr(expr: &Expr, env: &Env) -> Object {
+    match eval_expr_kind(expr, env) {
+        Object::Error(mut error) if error.span.is_none() => {
+            error.span = Some(expr.span);
+            Object::Error(error)
+        }
+        result => result,
+    }
+}
+
+fn eval_expr_kind(expr: &Expr, env: &Env) -> Object {
+    match &expr.kind {
+        ExprKind::Ident(ident) => eval_ident(ident, env),
+        ExprKind::IntLiteral(value) => Object::Integer(*value),
+        ExprKind::StringLiteral(value) => Object::Str(value.clone()),
+        ExprKind::Boolean(value) => Object::Boolean(*value),
+        ExprKind::PrefixExpr { expr, operator } => {
+            let right = eval_expr(expr, env);
+            if right.is_return_or_error() {
+                return right;
+            }
+
+            match operator {
+                Some(operator) => eval_prefix_expr(operator, right),
+                None => right,
+            }
+        }
+        ExprKind::InfixExpr { left, right, operator } => {
+            let left = eval_expr(left, env);
+            if left.is_return_or_error() {
+                return left;
+            }
+
+            let right = eval_expr(right, env);
+            if right.is_return_or_error() {
+                return right;
+            }
+
+            eval_infix_expr(operator, left, right)
+        }
+        ExprKind::If { condition, consequence, alternative } => {
+            let condition = eval_expr(condition, env);
+            if condition.is_return_or_error() {
+                return condition;
+            }
+
+            if condition.is_truthy() {
+                eval_block_stmt(consequence, env)
+            } else {
+                match alternative {
+                    Some(alternative) => eval_block_stmt(alternative, env),
+                    None => Object::Null,
+                }
+            }
+        }
+        ExprKind::Function { params, body } => Object::Function(Rc::new(Function {
+            params: params.clone(),
+            body: body.clone(),
+            env: Rc::clone(env),
+        })),
+        ExprKind::Call { callee, args } => {
+            let function = eval_expr(callee, env);
+            if function.is_return_or_error() {
+                return function;
+            }
+
+            match eval_exprs(args, env) {
+                Ok(args) => apply_function(function, args),
+                Err(error) => error,
+            }
+        }
+        ExprKind::Array(elements) => match eval_exprs(elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(error) => error,
+        },
+        ExprKind::Index { target, index } => {
+            let target = eval_expr(target, env);
+            if target.is_return_or_error() {
+                return target;
+            }
+
+            let index = eval_expr(index, env);
+            if index.is_return_or_error() {
+                return index;
+            }
+
+            eval_index_expr(target, index)
+        }
+        ExprKind::Hash(pairs) => eval_hash_expr(pairs, env),
+    }
+}
+
+fn eval_hash_expr(pairs: &[(Expr, Expr)], env: &Env) -> Object {
+    let mut hash = HashMap::with_capacity(pairs.len());
+
+    for (key_expr, value_expr) in pairs {
+        let key = eval_expr(key_expr, env);
+        if key.is_return_or_error() {
+            return key;
+        }
+
+        let key = match HashKey::try_from(&key) {
+            Ok(key) => key,
+            Err(mut error) => {
+                error.span = Some(key_expr.span);
+                return Object::Error(error);
+            }
+        };
+
+        let value = eval_expr(value_expr, env);
+        if value.is_return_or_error() {
+            return value;
+        }
+
+        hash.insert(key, value);
+    }
+
+    Object::Hash(hash)
+}
+
+/// Evaluates `exprs` left to right, stopping at the first error or `return`.
+fn eval_exprs(exprs: &[Expr], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(exprs.len());
+
+    for expr in exprs {
+        let value = eval_expr(expr, env);
+        if value.is_return_or_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+/// Arrays accept negative indices counting back from the end, so `xs[-1]` is the last element.
+/// Indices outside `-len..len` are an error.
+pub(crate) fn eval_index_expr(target: Object, index: Object) -> Object {
+    match (&target, &index) {
+        (Object::Array(elements), Object::Integer(i)) => {
+            let len = elements.len() as i64;
+            let position = if *i < 0 { *i + len } else { *i };
+
+            if position < 0 || position >= len {
+                return new_error(format!("index out of bounds: index {} but length is {}", i, len));
+            }
+
+            elements[position as usize].clone()
+        }
+        (Object::Array(_), _) => new_error(format!("array index must be INTEGER, got {}", index.type_name())),
+        (Object::Hash(pairs), _) => match HashKey::try_from(&index) {
+            Ok(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            Err(error) => Object::Error(error),
+        },
+        _ => new_error(format!("index operator not supported: {}", target.type_name())),
+    }
+}
+
+fn apply_function(function: Object, args: Vec<Object>) -> Object {
+    let function = match function {
+        Object::Function(function) => function,
+        Object::Builtin(builtin) => return (builtin.func)(args),
+        other => return new_error(format!("not a function: {}", other.type_name())),
+    };
+
+    if function.params.len() != args.len() {
+        return new_error(format!(
+            "wrong number of arguments: want={}, got={}",
+            function.params.len(),
+            args.len()
+        ));
+    }
+
+    let depth = CALL_DEPTH.with(Cell::get);
+    if depth >= MAX_CALL_DEPTH {
+        return new_error("stack overflow".to_string());
+    }
+
+    let call_env = Environment::new_enclosed(&function.env);
+    for (param, arg) in function.params.iter().zip(args) {
+        call_env.borrow_mut().set(&param.name, arg);
+    }
+
+    CALL_DEPTH.with(|call_depth| call_depth.set(depth + 1));
+    let result = eval_block_stmt(&function.body, &call_env);
+    CALL_DEPTH.with(|call_depth| call_depth.set(depth));
+
+    match result {
+        Object::ReturnValue(value) => *value,
+        result => result,
+    }
+}
+
+fn eval_ident(ident: &Ident, env: &Env) -> Object {
+    if let Some(value) = env.borrow().get(&ident.name) {
+        return value;
+    }
+
+    match builtins::lookup(&ident.name) {
+        Some(builtin) => Object::Builtin(builtin),
+        None => new_error(format!("identifier not found: {}", ident.name)),
+    }
+}
+
+pub(crate) fn eval_prefix_expr(operator: &PrefixOperator, right: Object) -> Object {
+    match operator {
+        PrefixOperator::Not => Object::Boolean(!right.is_truthy()),
+        PrefixOperator::Negate => match right {
+            Object::Integer(value) => Object::Integer(value.wrapping_neg()),
+            other => new_error(format!("unknown operator: -{}", other.type_name())),
+        },
+    }
+}
+
+pub(crate) fn eval_infix_expr(operator: &InfixOperator, left: Object, right: Object) -> Object {
+    match (&left, &right) {
+        (Object::Integer(left), Object::Integer(right)) => eval_integer_infix_expr(operator, *left, *right),
+        (Object::Str(left), Object::Str(right)) if *operator == InfixOperator::Add => {
+            Object::Str(format!("{}{}", left, right))
+        }
+        (Object::Boolean(left), Object::Boolean(right)) => match operator {
+            InfixOperator::Equals => Object::Boolean(left == right),
+            InfixOperator::NotEquals => Object::Boolean(left != right),
+            _ => new_error(format!("unknown operator: BOOLEAN {} BOOLEAN", operator)),
+        },
+        _ if left.type_name() != right.type_name() => new_error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        _ => match operator {
+            InfixOperator::Equals => Object::Boolean(left == right),
+            InfixOperator::NotEquals => Object::Boolean(left != right),
+            _ => new_error(format!(
+                "unknown operator: {} {} {}",
+                left.type_name(),
+                operator,
+                right.type_name()
+            )),
+        },
+    }
+}
+
+fn eval_integer_infix_expr(operator: &InfixOperator, left: i64, right: i64) -> Object {
+    match operator {
+        InfixOperator::Add => Object::Integer(left.wrapping_add(right)),
+        InfixOperator::Sub => Object::Integer(left.wrapping_sub(right)),
+        InfixOperator::Mul => Object::Integer(left.wrapping_mul(right)),
+        InfixOperator::Div => {
+            if right == 0 {
+                new_error("division by zero".to_string())
+            } else {
+                Object::Integer(left.wrapping_div(right))
+            }
+        }
+        InfixOperator::Equals => Object::Boolean(left == right),
+        InfixOperator::NotEquals => Object::Boolean(left != right),
+        InfixOperator::LessThan => Object::Boolean(left < right),
+        InfixOperator::GreaterThan => Object::Boolean(left > right),
+    }
+}
+
+fn new_error(message: String) -> Object {
+    Object::Error(RuntimeError::new(message))
+}
+
+#[cfg(test)]
+mod eval_tests {
+    use crate::eval::environment::Environment;
+    use crate::eval::eval_program;
+    use std::collections::HashMap;
+    use std::thread;
+
+    use crate::eval::object::{HashKey, Object};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::token::Span;
+
+    fn run(input: &str) -> Object {
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        eval_program(&program.unwrap(), &Environment::new())
+    }
+
+    #[test]
+    fn test_eval_integer_expressions() {
+        let test_cases: Vec<(&str, i64)> = vec![
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Integer(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expressions() {
+        let test_cases: Vec<(&str, bool)> = vec![
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 > 1", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 2", false),
+            ("1 != 2", true),
+            ("true", true),
+            ("false", false),
+            ("true == true", true),
+            ("false == false", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) == true", false),
+            ("(1 < 2) == (2 > 1)", true),
+            ("1 < 2 == 2 > 1", true),
+            ("1 < 2 != 2 > 1", false),
+            ("!5 == !6", true),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Boolean(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_bang_operator() {
+        let test_cases: Vec<(&str, bool)> = vec![
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+            ("!0", false),
+            ("!!0", true),
+            ("!-5", false),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Boolean(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_let_statements() {
+        let test_cases: Vec<(&str, i64)> = vec![
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Integer(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_return_statements() {
+        let test_cases: Vec<(&str, Object)> = vec![
+            ("return 10;", Object::Integer(10)),
+            ("return 10; 9;", Object::Integer(10)),
+            ("return 2 * 5; 9;", Object::Integer(10)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            ("return;", Object::Null),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", Object::Integer(10)),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(expected, run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_if_else_expressions() {
+        let test_cases: Vec<(&str, Object)> = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(expected, run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_string_expressions() {
+        let test_cases: Vec<(&str, Object)> = vec![
+            (r#""Hello World!""#, Object::Str("Hello World!".to_string())),
+            (r#""Hello" + " " + "World!""#, Object::Str("Hello World!".to_string())),
+            (r#"let greet = fn(name) { "Hi, " + name }; greet("\u{1F600}\n")"#, Object::Str("Hi, \u{1F600}\n".to_string())),
+            (r#""a" == "a""#, Object::Boolean(true)),
+            (r#""a" != "a""#, Object::Boolean(false)),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(expected, run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_array_literals() {
+        assert_eq!(
+            Object::Array(vec![Object::Integer(1), Object::Integer(4), Object::Integer(6)]),
+            run("[1, 2 * 2, 3 + 3]")
+        );
+        assert_eq!("[1, true, [a]]", run(r#"[1, true, ["a"]]"#).to_string());
+    }
+
+    #[test]
+    fn test_eval_index_expressions() {
+        let test_cases: Vec<(&str, i64)> = vec![
+            ("[1, 2, 3][0]", 1),
+            ("[1, 2, 3][1]", 2),
+            ("[1, 2, 3][2]", 3),
+            ("let i = 0; [1][i];", 1),
+            ("[1, 2, 3][1 + 1];", 3),
+            ("let myArray = [1, 2, 3]; myArray[2];", 3),
+            ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", 6),
+            ("let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]", 2),
+            ("[[1, 2], [3, 4]][1][0]", 3),
+            ("[1, 2, 3][-1]", 3),
+            ("[1, 2, 3][-3]", 1),
+            ("let first = fn(xs) { xs[0] }; first([7, 8])", 7),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Integer(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_hash_literals() {
+        let input = r#"let two = "two";
+{
+    "one": 10 - 9,
+    two: 1 + 1,
+    "thr" + "ee": 6 / 2,
+    4: 4,
+    true: 5,
+    false: 6
+}"#;
+
+        let expected: HashMap<HashKey, Object> = vec![
+            (HashKey::Str("one".to_string()), Object::Integer(1)),
+            (HashKey::Str("two".to_string()), Object::Integer(2)),
+            (HashKey::Str("three".to_string()), Object::Integer(3)),
+            (HashKey::Integer(4), Object::Integer(4)),
+            (HashKey::Boolean(true), Object::Integer(5)),
+            (HashKey::Boolean(false), Object::Integer(6)),
+        ].into_iter().collect();
+
+        assert_eq!(Object::Hash(expected), run(input));
+        assert_eq!("{1: a, true: [2], b: c}", run(r#"{"b": "c", true: [2], 1: "a"}"#).to_string());
+        assert_eq!(r#"{1: "a", true: ["\n"], "b": "c"}"#, run(r#"{"b": "c", true: ["\n"], 1: "a"}"#).inspect());
+    }
+
+    #[test]
+    fn test_eval_hash_index_expressions() {
+        let test_cases: Vec<(&str, Object)> = vec![
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            (r#"{"a": 1, "a": 2}["a"]"#, Object::Integer(2)),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(expected, run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_builtin_functions() {
+        let test_cases: Vec<(&str, Object)> = vec![
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("héllo")"#, Object::Integer(5)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            (r#"len({"a": 1})"#, Object::Integer(1)),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            ("rest([1, 2, 3])", Object::Array(vec![Object::Integer(2), Object::Integer(3)])),
+            ("rest([])", Object::Null),
+            ("push([], 1)", Object::Array(vec![Object::Integer(1)])),
+            ("let a = [1]; push(a, 2); a", Object::Array(vec![Object::Integer(1)])),
+            ("puts(1, 2)", Object::Null),
+            ("let len = fn(x) { 42 }; len([1])", Object::Integer(42)),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(expected, run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_function_object() {
+        let result = run("fn(x) { x + 2; };");
+
+        assert!(matches!(&result, Object::Function(function)
+            if function.params.len() == 1 && function.params[0].name == "x" && function.body.statements.len() == 1));
+        assert_eq!("fn(x) { ... }", result.to_string());
+    }
+
+    #[test]
+    fn test_eval_function_application() {
+        let test_cases: Vec<(&str, i64)> = vec![
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+            ("let early = fn() { return 1; 2; }; early() + 10;", 11),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Integer(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_closures() {
+        let test_cases: Vec<(&str, i64)> = vec![
+            ("let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)", 5),
+            ("let curry = fn(f) { fn(a) { fn(b) { f(a, b) } } }; curry(fn(a, b) { a - b })(10)(3)", 7),
+            ("let counter = fn(n) { {\"value\": n, \"next\": fn() { counter(n + 1) }} }; counter(0)[\"next\"]()[\"next\"]()[\"value\"]", 2),
+            ("let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5)", 120),
+            ("let outer = fn() { let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5) }; outer()", 120),
+            // Parameters and inner bindings shadow outer ones without changing them.
+            ("let x = 1; let f = fn(x) { fn() { x } }; f(5)() + x", 6),
+            ("let x = 1; let f = fn() { let x = 2; fn() { x } }; f()() + x", 3),
+            // A closure sees its binding, not a copy: rebinding it in the same scope is visible.
+            ("let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g() }; f()", 2),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(Object::Integer(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_error_spans() {
+        let test_cases: Vec<(&str, Span)> = vec![
+            ("foobar", Span::new(0, 6, 1, 1)),
+            ("1 + 2 * foo", Span::new(8, 11, 1, 9)),
+            ("let x = 5;\nx + true", Span::new(11, 19, 2, 1)),
+            ("let f = fn() { 1 / 0 };\nf()", Span::new(15, 20, 1, 16)),
+            ("fn(a) { a }(1, 2)", Span::new(0, 17, 1, 1)),
+            ("{1: 2, [3]: 4}", Span::new(7, 10, 1, 8)),
+        ];
+
+        for (input, expected) in test_cases {
+            let result = run(input);
+            assert!(matches!(&result, Object::Error(error) if error.span == Some(expected)),
+                "input: {}, got: {:?}", input, result);
+        }
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let test_cases: Vec<(&str, &str)> = vec![
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("true < false;", "unknown operator: BOOLEAN < BOOLEAN"),
+            ("1 < 2 < 3", "type mismatch: BOOLEAN < INTEGER"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { true + false; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { if (10 > 1) { return true + false; } return 1; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("foobar", "identifier not found: foobar"),
+            ("10 / 0", "division by zero"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""Hello" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("[1, 2, 3][3]", "index out of bounds: index 3 but length is 3"),
+            ("[1, 2, 3][-4]", "index out of bounds: index -4 but length is 3"),
+            ("[][0]", "index out of bounds: index 0 but length is 0"),
+            ("[1][true]", "array index must be INTEGER, got BOOLEAN"),
+            ("1[0]", "index operator not supported: INTEGER"),
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            (r#"len("one", "two")"#, "wrong number of arguments to `len`: want=1, got=2"),
+            ("first(1)", "argument to `first` must be ARRAY, got INTEGER"),
+            ("push(1, 1)", "argument to `push` must be ARRAY, got INTEGER"),
+            (r#"{"name": "Monkey"}[fn(x) { x }];"#, "unusable as hash key: FUNCTION"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{{}: 2}", "unusable as hash key: HASH"),
+            ("5(1)", "not a function: INTEGER"),
+            ("fn(x, y) { x }(1)", "wrong number of arguments: want=2, got=1"),
+            ("fn() { y }()", "identifier not found: y"),
+            ("5; 10 / 0; 5", "division by zero"),
+        ];
+
+        for (input, expected) in test_cases {
+            let result = run(input);
+            assert!(matches!(&result, Object::Error(error) if error.message == expected),
+                "input: {}, got: {:?}", input, result);
+        }
+    }
+
+    #[test]
+    fn test_eval_stack_overflow() {
+        // Test threads get less native stack than the main thread the limit is meant for.
+        let outcome = thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                let deep = run("let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(255)");
+                let runaway = run("let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(256)");
+                let after = run("let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(255)");
+                (deep.inspect(), runaway.inspect(), after.inspect())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(("0".to_string(), "ERROR: stack overflow".to_string(), "0".to_string()), outcome);
+    }
+}
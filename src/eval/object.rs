@@ -0,0 +1,280 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+use crate::compiler::code::{Capture, Instructions, SourceMap};
+use crate::eval::environment::Env;
+use crate::parser::ast::{BlockStatement, Ident};
+use crate::token::Span;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Str(String),
+    Boolean(bool),
+    Array(Vec<Object>),
+    Hash(HashMap<HashKey, Object>),
+    Null,
+    ReturnValue(Box<Object>),
+    Function(Rc<Function>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Builtin(Builtin),
+    Error(RuntimeError),
+}
+
+/// Error raised while evaluating a program. `span` points at the innermost expression that
+/// failed, once evaluation has passed through it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl RuntimeError {
+    pub fn new(message: String) -> RuntimeError {
+        RuntimeError { message, span: None }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for RuntimeError {}
+
+/// The subset of objects that can be used as hash keys. Functions, arrays and hashes are not
+/// hashable, so `HashKey::try_from` rejects them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    Str(String),
+}
+
+impl TryFrom<&Object> for HashKey {
+    type Error = RuntimeError;
+
+    fn try_from(object: &Object) -> Result<Self, Self::Error> {
+        match object {
+            Object::Integer(value) => Ok(HashKey::Integer(*value)),
+            Object::Boolean(value) => Ok(HashKey::Boolean(*value)),
+            Object::Str(value) => Ok(HashKey::Str(value.clone())),
+            other => Err(RuntimeError::new(format!("unusable as hash key: {}", other.type_name()))),
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Integer(value) => Object::Integer(value),
+            HashKey::Boolean(value) => Object::Boolean(value),
+            HashKey::Str(value) => Object::Str(value),
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::Integer(value) => write!(f, "{}", value),
+            HashKey::Boolean(value) => write!(f, "{}", value),
+            HashKey::Str(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Function {
+    pub params: Vec<Ident>,
+    pub body: BlockStatement,
+    pub env: Env,
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // The captured environment may contain this very function, so it is left out.
+        f.debug_struct("Function")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        // A function is only equal to itself: each evaluation of a literal makes a new one.
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A function literal compiled to bytecode. It lives in the constant pool; parameters come first
+/// among its locals, and `captures` lists its free variables in the order `OpGetFree` numbers them.
+#[derive(Clone, Debug)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize,
+    pub params: Vec<String>,
+    pub captures: Vec<Capture>,
+    pub source_map: SourceMap,
+}
+
+impl PartialEq for CompiledFunction {
+    fn eq(&self, other: &Self) -> bool {
+        // Like spans on the AST, the source map is left out of comparisons.
+        self.instructions == other.instructions
+            && self.num_locals == other.num_locals
+            && self.params == other.params
+            && self.captures == other.captures
+    }
+}
+
+/// A compiled function together with the variables it captured when the VM created it.
+#[derive(Clone)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // A captured variable may hold this very closure, so they are left out.
+        f.debug_struct("Closure").field("function", &self.function).finish()
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        // Like functions, a closure is only equal to itself: `OpClosure` makes a new one each time.
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A variable captured by a closure. While the call that owns it runs, it refers to the variable's
+/// stack slot, so the closure sees the call rebind it; when the call returns it keeps the last
+/// value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Object),
+}
+
+pub type BuiltinFunction = fn(Vec<Object>) -> Object;
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFunction,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builtin").field("name", &self.name).finish()
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Str(_) => "STRING",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Function(_) => "FUNCTION",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            // Closures are what function literals evaluate to in the VM, so they share the name.
+            Object::Closure(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Error(_) => "ERROR",
+        }
+    }
+
+    /// The form the REPL echoes back: like `Display`, but strings are quoted and escaped so
+    /// `"1"` and `1` can be told apart, including inside arrays and hashes.
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::Str(value) => format!("{:?}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.inspect()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let mut pairs: Vec<(&HashKey, &Object)> = pairs.iter().collect();
+                pairs.sort_by(|a, b| a.0.cmp(b.0));
+
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", Object::from((*key).clone()).inspect(), value.inspect()))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+            Object::ReturnValue(value) => value.inspect(),
+            other => other.to_string(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    /// Whether evaluating `self` ends the enclosing function: it is an error or a returned value,
+    /// which are passed up unchanged through every expression around them.
+    pub fn is_return_or_error(&self) -> bool {
+        matches!(self, Object::ReturnValue(_) | Object::Error(_))
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Object::Null => false,
+            Object::Boolean(value) => *value,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Str(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                // Hash maps have no order of their own, so sort to keep the output stable.
+                let mut pairs: Vec<(&HashKey, &Object)> = pairs.iter().collect();
+                pairs.sort_by(|a, b| a.0.cmp(b.0));
+
+                let pairs: Vec<String> = pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Function(function) => {
+                let params: Vec<&str> = function.params.iter().map(|param| param.name.as_str()).collect();
+                write!(f, "fn({}) {{ ... }}", params.join(", "))
+            }
+            Object::CompiledFunction(function) => write!(f, "fn({}) {{ ... }}", function.params.join(", ")),
+            Object::Closure(closure) => write!(f, "fn({}) {{ ... }}", closure.function.params.join(", ")),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
+            Object::Error(error) => write!(f, "ERROR: {}", error),
+        }
+    }
+}
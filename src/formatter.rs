@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+
+use crate::lexer::Lexer;
+use crate::parser::ast::{escape, BlockStatement, Expr, ExprKind, ExprPrecedence, InfixOperator, Program, Stmt, StmtKind};
+use crate::parser::{ParseError, Parser};
+use crate::token::{Span, Token};
+
+const INDENT: &str = "    ";
+
+/// Formats Monkey source in the canonical style: one statement per line ending in `;`, four space
+/// indentation, `{` on the same line as the construct it opens, single spaces around infix
+/// operators and after commas, and parentheses only where precedence requires them. Runs of blank
+/// lines between statements are collapsed to one.
+///
+/// Comments are kept: those on lines of their own stay above the statement or closing `}` that
+/// follows them, block comments right before an expression or between `}` and `else` stay where
+/// they are, and the rest are moved to the end of the line of the innermost statement they appear
+/// in.
+///
+/// Formatting is idempotent: formatting the output again gives the same output.
+pub fn format(source: &str) -> Result<String, Vec<ParseError>> {
+    let program = Parser::new(Lexer::new(source)).parse_program()?;
+
+    let (comments, braces) = scan(source);
+    let mut formatter = Formatter { source, comments, braces, next_comment: 0, out: String::new(), depth: 0 };
+    formatter.program(&program);
+    Ok(formatter.out)
+}
+
+/// Collects the comments in `source`, and the offset of each `{` mapped to the offset of the `}`
+/// that closes it.
+fn scan(source: &str) -> (Vec<(Span, String)>, BTreeMap<usize, usize>) {
+    let mut lexer = Lexer::new(source).with_comments();
+    let mut comments = vec![];
+    let mut braces = BTreeMap::new();
+    let mut open = vec![];
+
+    loop {
+        let tok = lexer.next_token();
+        match tok.token {
+            Token::Comment(text) => comments.push((tok.span, text)),
+            Token::LBrace => open.push(tok.span.start),
+            Token::RBrace => {
+                if let Some(start) = open.pop() {
+                    braces.insert(start, tok.span.start);
+                }
+            }
+            Token::Eof => return (comments, braces),
+            _ => {}
+        }
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<(Span, String)>,
+    braces: BTreeMap<usize, usize>,
+    next_comment: usize,
+    out: String,
+    depth: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn program(&mut self, program: &Program) {
+        let last_line = self.statements(&program.statements, usize::MAX);
+
+        // Comments after the last statement.
+        self.leading_comments(usize::MAX, last_line);
+    }
+
+    /// Writes `statements` one per line at the current depth, along with the comments around
+    /// them that come before `limit`. Returns the last source line written.
+    fn statements(&mut self, statements: &[Stmt], limit: usize) -> Option<usize> {
+        let mut last_line = None;
+
+        for stmt in statements {
+            last_line = self.leading_comments(stmt.span.start, last_line);
+            self.blank_line_between(last_line, stmt.span.line);
+
+            self.out.push_str(&INDENT.repeat(self.depth));
+            self.stmt(stmt);
+
+            let end_line = end_line(stmt.span, &self.source[stmt.span.start..stmt.span.end.min(self.source.len())]);
+            last_line = Some(self.trailing_comments(stmt.span.end, end_line, limit).max(end_line));
+            self.out.push('\n');
+        }
+
+        last_line
+    }
+
+    /// Writes the comments before `offset` on lines of their own.
+    fn leading_comments(&mut self, offset: usize, mut last_line: Option<usize>) -> Option<usize> {
+        while let Some((span, text)) = self.comments.get(self.next_comment).cloned() {
+            if span.start >= offset {
+                break;
+            }
+
+            self.blank_line_between(last_line, span.line);
+            self.out.push_str(&INDENT.repeat(self.depth));
+            self.out.push_str(&text);
+            self.out.push('\n');
+
+            last_line = Some(end_line(span, &text));
+            self.next_comment += 1;
+        }
+
+        last_line
+    }
+
+    /// Appends the comments that sit inside the statement ending at `end` (and were not claimed by
+    /// a nested block) or follow it on its last line before `limit`. Returns the last source line
+    /// they cover.
+    fn trailing_comments(&mut self, end: usize, line: usize, limit: usize) -> usize {
+        let mut last_line = 0;
+        let mut after_line_comment = false;
+
+        while let Some((span, text)) = self.comments.get(self.next_comment).cloned() {
+            if span.start >= limit || (span.start >= end && span.line != line) {
+                break;
+            }
+
+            // Nothing can follow a `//` comment on the same line.
+            if after_line_comment {
+                self.out.push('\n');
+                self.out.push_str(&INDENT.repeat(self.depth));
+            } else {
+                self.out.push(' ');
+            }
+            self.out.push_str(&text);
+
+            after_line_comment = text.starts_with("//");
+            last_line = end_line(span, &text);
+            self.next_comment += 1;
+        }
+
+        last_line
+    }
+
+    /// Writes the block comments before `offset` in place, each followed by a space. Stops at a
+    /// `//` comment, since it would swallow the code after it.
+    fn inline_comments(&mut self, offset: usize) {
+        while let Some((span, text)) = self.comments.get(self.next_comment).cloned() {
+            if span.start >= offset || text.starts_with("//") {
+                break;
+            }
+
+            self.out.push_str(&text);
+            self.out.push(' ');
+            self.next_comment += 1;
+        }
+    }
+
+    fn blank_line_between(&mut self, last_line: Option<usize>, line: usize) {
+        if last_line.is_some_and(|last_line| line > last_line + 1) {
+            self.out.push('\n');
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                self.out.push_str("let ");
+                self.out.push_str(&let_stmt.ident.name);
+                self.out.push_str(" = ");
+                self.expr(&let_stmt.expr);
+            }
+            StmtKind::Return(Some(expr)) => {
+                self.out.push_str("return ");
+                self.expr(expr);
+            }
+            StmtKind::Return(None) => self.out.push_str("return"),
+            StmtKind::Expr(expr) => self.expr(expr),
+        }
+
+        self.out.push(';');
+    }
+
+    /// Writes `block`, whose `{` is the first one at or after the source offset `from`. Comments
+    /// before its `}` stay inside it.
+    fn block(&mut self, block: &BlockStatement, from: usize) -> usize {
+        let close = self.braces.range(from..).next().map_or(usize::MAX, |(_, close)| *close);
+        let has_comments = self.comments.get(self.next_comment).is_some_and(|(span, _)| span.start < close);
+
+        if block.statements.is_empty() && !has_comments {
+            self.out.push_str("{}");
+            return close;
+        }
+
+        self.out.push_str("{\n");
+        self.depth += 1;
+        let last_line = self.statements(&block.statements, close);
+        self.leading_comments(close, last_line);
+        self.depth -= 1;
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push('}');
+        close
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.inline_comments(expr.span.start);
+
+        match &expr.kind {
+            ExprKind::Ident(ident) => self.out.push_str(&ident.name),
+            ExprKind::IntLiteral(value) => self.out.push_str(&value.to_string()),
+            ExprKind::StringLiteral(value) => {
+                self.out.push('"');
+                self.out.push_str(&escape(value));
+                self.out.push('"');
+            }
+            ExprKind::Boolean(value) => self.out.push_str(&value.to_string()),
+            ExprKind::PrefixExpr { expr, operator } => {
+                if let Some(operator) = operator {
+                    self.out.push_str(&operator.to_string());
+                }
+                self.operand(expr, precedence(expr) < ExprPrecedence::PREFIX);
+            }
+            ExprKind::InfixExpr { left, right, operator } => {
+                let own = infix_precedence(operator);
+                // Infix operators are left associative, so an equal-precedence right operand
+                // needs parentheses to keep its grouping.
+                self.operand(left, precedence(left) < own);
+                self.out.push(' ');
+                self.out.push_str(&operator.to_string());
+                self.out.push(' ');
+                self.operand(right, precedence(right) <= own);
+            }
+            ExprKind::If { condition, consequence, alternative } => {
+                self.out.push_str("if (");
+                self.expr(condition);
+                self.out.push_str(") ");
+                let close = self.block(consequence, condition.span.end);
+                if let Some(alternative) = alternative {
+                    // Comments between `}` and `else` belong to neither block.
+                    let open = self.braces.range(close.saturating_add(1)..).next().map_or(usize::MAX, |(open, _)| *open);
+                    let mut line_start = false;
+                    while let Some((span, text)) = self.comments.get(self.next_comment).cloned() {
+                        if span.start >= open {
+                            break;
+                        }
+
+                        if !line_start {
+                            self.out.push(' ');
+                        }
+                        self.out.push_str(&text);
+                        line_start = text.starts_with("//");
+                        if line_start {
+                            self.out.push('\n');
+                            self.out.push_str(&INDENT.repeat(self.depth));
+                        }
+                        self.next_comment += 1;
+                    }
+                    self.out.push_str(if line_start { "else " } else { " else " });
+                    self.block(alternative, open);
+                }
+            }
+            ExprKind::Function { params, body } => {
+                let params: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+                self.out.push_str("fn(");
+                self.out.push_str(&params.join(", "));
+                self.out.push_str(") ");
+                self.block(body, expr.span.start);
+            }
+            ExprKind::Call { callee, args } => {
+                self.operand(callee, precedence(callee) < ExprPrecedence::CALL);
+                self.out.push('(');
+                self.list(args);
+                self.out.push(')');
+            }
+            ExprKind::Array(elements) => {
+                self.out.push('[');
+                self.list(elements);
+                self.out.push(']');
+            }
+            ExprKind::Index { target, index } => {
+                self.operand(target, precedence(target) < ExprPrecedence::CALL);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            ExprKind::Hash(pairs) => {
+                self.out.push('{');
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key);
+                    self.out.push_str(": ");
+                    self.expr(value);
+                }
+                self.out.push('}');
+            }
+        }
+    }
+
+    fn operand(&mut self, expr: &Expr, parenthesize: bool) {
+        if parenthesize {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn list(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+}
+
+/// Line on which the source `text` starting at `span` ends.
+fn end_line(span: Span, text: &str) -> usize {
+    span.line + text.matches('\n').count()
+}
+
+/// How tightly `expr` binds. Anything that is not an operator is atomic.
+fn precedence(expr: &Expr) -> ExprPrecedence {
+    match &expr.kind {
+        ExprKind::PrefixExpr { operator: Some(_), .. } => ExprPrecedence::PREFIX,
+        ExprKind::PrefixExpr { operator: None, expr } => precedence(expr),
+        ExprKind::InfixExpr { operator, .. } => infix_precedence(operator),
+        ExprKind::Call { .. } => ExprPrecedence::CALL,
+        _ => ExprPrecedence::INDEX,
+    }
+}
+
+fn infix_precedence(operator: &InfixOperator) -> ExprPrecedence {
+    match operator {
+        InfixOperator::Equals | InfixOperator::NotEquals => ExprPrecedence::EQUALS,
+        InfixOperator::LessThan | InfixOperator::GreaterThan => ExprPrecedence::COMPARE,
+        InfixOperator::Add | InfixOperator::Sub => ExprPrecedence::SUM,
+        InfixOperator::Mul | InfixOperator::Div => ExprPrecedence::PRODUCT,
+    }
+}
+
+#[cfg(test)]
+mod formatter_tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::formatter::format;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    /// Every `testdata/fmt/<name>.mk` must format to `testdata/fmt/<name>.golden.mk`.
+    #[test]
+    fn test_format_golden_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join("fmt");
+        let mut count = 0;
+
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            if !name.ends_with(".mk") || name.ends_with(".golden.mk") {
+                continue;
+            }
+
+            let input = fs::read_to_string(&path).unwrap();
+            let golden = fs::read_to_string(path.with_extension("golden.mk")).unwrap();
+            let formatted = format(&input).unwrap_or_else(|e| panic!("{}: {:?}", name, e));
+
+            assert_eq!(golden, formatted, "{}", name);
+            assert_eq!(golden, format(&formatted).unwrap(), "{} is not idempotent", name);
+
+            let original = Parser::new(Lexer::new(&input)).parse_program().unwrap();
+            let reparsed = Parser::new(Lexer::new(&formatted)).parse_program().unwrap();
+            assert_eq!(original, reparsed, "{} changed meaning", name);
+
+            count += 1;
+        }
+
+        assert!(count > 0, "no golden files in {}", dir.display());
+    }
+
+    #[test]
+    fn test_format_parenthesises_only_where_needed() {
+        let tests = vec![
+            ("((a + b)) * c", "(a + b) * c;\n"),
+            ("a - (b - c)", "a - (b - c);\n"),
+            ("(a - b) - c", "a - b - c;\n"),
+            ("-(a + b)", "-(a + b);\n"),
+            ("(-a)[0]", "(-a)[0];\n"),
+            ("(f(1))[0]", "f(1)[0];\n"),
+            ("(a == b) == c", "a == b == c;\n"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, format(input).unwrap(), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_rejects_invalid_source() {
+        assert_eq!(1, format("let = 1;").unwrap_err().len());
+    }
+}
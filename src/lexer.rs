@@ -1,9 +1,11 @@
-use crate::token::Token;
+use crate::token::{Span, SpannedToken, Token};
 use crate::token::Token::*;
 use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
 
 lazy_static! {
-    static ref KEYWORDS: HashMap<String, Token> = {
+    pub static ref KEYWORDS: HashMap<String, Token> = {
         let mut keywords_lookup = HashMap::new();
         keywords_lookup.insert("fn".to_string(), Function);
         keywords_lookup.insert("let".to_string(), Let);
@@ -16,20 +18,30 @@ lazy_static! {
     };
 }
 
-pub(crate) struct Lexer<'a> {
+pub struct Lexer<'a> {
     input: &'a str,
     position: usize,
     read_position: usize,
     ch: u8,
+
+    line: usize,
+    col: usize,
+
+    emit_comments: bool,
 }
 
 impl<'a> Lexer<'a> {
-    pub(crate) fn new(input: &str) -> Lexer {
+    pub fn new(input: &'a str) -> Lexer<'a> {
         let mut lexer = Lexer {
             input,
             position: 0,
             read_position: 0,
             ch: 0,
+
+            line: 1,
+            col: 0,
+
+            emit_comments: false,
         };
 
         lexer.consume_char();
@@ -37,7 +49,21 @@ impl<'a> Lexer<'a> {
         lexer
     }
 
+    /// Emits comments as `Token::Comment` trivia instead of skipping them, for tools such as the
+    /// formatter that need to recover them. The parser does not accept comment tokens.
+    pub fn with_comments(mut self) -> Lexer<'a> {
+        self.emit_comments = true;
+        self
+    }
+
     fn consume_char(&mut self) {
+        if self.ch == b'\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+
         if self.read_position >= self.input.len() {
             self.ch = 0
         } else {
@@ -48,9 +74,31 @@ impl<'a> Lexer<'a> {
         self.read_position += 1;
     }
 
-    pub(crate) fn next_token(&mut self) -> Token {
-        self.skip_whitespaces();
+    pub fn next_token(&mut self) -> SpannedToken {
+        loop {
+            self.skip_whitespaces();
 
+            let start = self.position.min(self.input.len());
+            let (line, col) = (self.line, self.col);
+
+            let token = match (self.ch, self.peek_byte()) {
+                (b'/', b'/') => self.consume_line_comment(),
+                (b'/', b'*') => self.consume_block_comment(),
+                _ => self.read_token(),
+            };
+
+            // Skipped comments still surface their errors.
+            if matches!(token, Comment(_)) && !self.emit_comments {
+                continue;
+            }
+
+            let end = self.position.min(self.input.len());
+
+            return SpannedToken { token, span: Span::new(start, end, line, col) };
+        }
+    }
+
+    fn read_token(&mut self) -> Token {
         let tok = match self.ch {
             b'=' => {
                 if self.peek_char(b'=') {
@@ -78,9 +126,13 @@ impl<'a> Lexer<'a> {
             b')' => RParen,
             b'{' => LBrace,
             b'}' => RBrace,
+            b'[' => LBracket,
+            b']' => RBracket,
             b',' => Comma,
             b';' => Semicolon,
-            0 => Eof,
+            b':' => Colon,
+            b'"' => return self.consume_string(),
+            0 if self.position >= self.input.len() => return Eof,
 
             _ => {
                 if is_letter(self.ch) {
@@ -89,7 +141,7 @@ impl<'a> Lexer<'a> {
                 } else if is_digit(self.ch) {
                     return self.consume_number();
                 } else {
-                    Illegal
+                    return self.consume_illegal();
                 }
             }
         };
@@ -105,6 +157,52 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn consume_line_comment(&mut self) -> Token {
+        let start = self.position;
+        while !(self.ch == b'\n' || self.position >= self.input.len() || (self.ch == b'\r' && self.peek_char(b'\n'))) {
+            self.consume_char();
+        }
+
+        Comment(self.input[start..self.position].to_string())
+    }
+
+    /// Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn consume_block_comment(&mut self) -> Token {
+        let start = self.position;
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_byte()) {
+                (0, _) if self.position >= self.input.len() => return Error(LexError::UnterminatedComment),
+                (b'/', b'*') => {
+                    depth += 1;
+                    self.consume_char();
+                }
+                (b'*', b'/') => {
+                    depth -= 1;
+                    self.consume_char();
+                    if depth == 0 {
+                        self.consume_char();
+                        return Comment(self.input[start..self.position].to_string());
+                    }
+                }
+                _ => {}
+            }
+            self.consume_char();
+        }
+    }
+
+    /// Takes the whole character, so a non-ASCII one is a single token whose span stays on
+    /// character boundaries.
+    fn consume_illegal(&mut self) -> Token {
+        self.consume_char();
+        while self.ch & 0xC0 == 0x80 {
+            self.consume_char();
+        }
+
+        Illegal
+    }
+
     fn consume_ident(&mut self) -> String {
         let current_position = self.position;
         while is_letter(self.ch) {
@@ -121,16 +219,138 @@ impl<'a> Lexer<'a> {
             self.consume_char()
         }
 
-        Int(self.input[start_position..self.position]
-            .parse::<i64>()
-            .unwrap())
+        match self.input[start_position..self.position].parse::<i64>() {
+            Ok(value) => Int(value),
+            Err(_) => Error(LexError::IntegerOverflow),
+        }
+    }
+
+    fn consume_string(&mut self) -> Token {
+        // Skip the opening quote.
+        self.consume_char();
+
+        let mut bytes = vec![];
+        let mut error = None;
+
+        loop {
+            if self.position >= self.input.len() {
+                return Error(LexError::UnterminatedString);
+            }
+
+            match self.ch {
+                b'"' => break,
+                b'\\' => {
+                    self.consume_char();
+                    match self.consume_escape() {
+                        Ok(ch) => bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+                        // Keep going so the whole literal is consumed, but report the first bad escape.
+                        Err(e) => {
+                            error.get_or_insert(e);
+                        }
+                    }
+                }
+                byte => {
+                    bytes.push(byte);
+                    self.consume_char();
+                }
+            }
+        }
+
+        // Skip the closing quote.
+        self.consume_char();
+
+        match error {
+            Some(e) => Error(e),
+            // Only whole UTF-8 sequences are copied, so the bytes are always valid.
+            None => Str(String::from_utf8(bytes).unwrap()),
+        }
+    }
+
+    /// Consumes the escape sequence following a backslash and returns the character it denotes.
+    fn consume_escape(&mut self) -> Result<char, LexError> {
+        let escaped = match self.ch {
+            b'n' => '\n',
+            b't' => '\t',
+            b'r' => '\r',
+            b'"' => '"',
+            b'\\' => '\\',
+            b'u' => {
+                self.consume_char();
+                return self.consume_unicode_escape();
+            }
+            _ if self.position >= self.input.len() => return Err(LexError::UnterminatedString),
+            other => {
+                let escaped = self.input[self.position..].chars().next().unwrap_or(other as char);
+                // Leave quotes and backslashes to the string loop so `"\"` still terminates.
+                self.consume_char();
+                return Err(LexError::InvalidEscape(escaped));
+            }
+        };
+
+        self.consume_char();
+
+        Ok(escaped)
+    }
+
+    fn consume_unicode_escape(&mut self) -> Result<char, LexError> {
+        if self.ch != b'{' {
+            return Err(LexError::InvalidUnicodeEscape);
+        }
+        self.consume_char();
+
+        let start = self.position;
+        while self.ch.is_ascii_hexdigit() {
+            self.consume_char();
+        }
+        let digits = &self.input[start..self.position.min(self.input.len())];
+
+        if self.ch != b'}' {
+            return Err(LexError::InvalidUnicodeEscape);
+        }
+        self.consume_char();
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(LexError::InvalidUnicodeEscape);
+        }
+
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(LexError::InvalidUnicodeEscape)
     }
 
     fn peek_char(&self, peek: u8) -> bool {
+        self.peek_byte() == peek
+    }
+
+    fn peek_byte(&self) -> u8 {
         if self.read_position >= self.input.len() {
-            0 == peek
+            0
         } else {
-            self.input.as_bytes()[self.read_position] == peek
+            self.input.as_bytes()[self.read_position]
+        }
+    }
+}
+
+/// Problems found while reading a token. They are emitted as `Token::Error` so the parser can
+/// report them with the token's span.
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub enum LexError {
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape(char),
+    InvalidUnicodeEscape,
+    IntegerOverflow,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnterminatedString => write!(f, "unterminated string literal"),
+            LexError::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexError::InvalidEscape(ch) => write!(f, "unknown escape sequence `\\{}`", ch),
+            LexError::InvalidUnicodeEscape => write!(f, "invalid unicode escape, expected `\\u{{XXXX}}`"),
+            LexError::IntegerOverflow => write!(f, "integer literal is too large, the maximum is {}", i64::MAX),
         }
     }
 }
@@ -152,7 +372,8 @@ fn is_letter(ch: u8) -> bool {
 
 #[cfg(test)]
 mod lexer_tests {
-    use crate::lexer::Lexer;
+    use crate::lexer::{LexError, Lexer};
+    use crate::token::{Span, SpannedToken};
     use crate::token::Token::*;
 
     #[test]
@@ -165,7 +386,7 @@ let add = fn(x, y) {
 };
 
 let result = add(five, ten);
-!-/*5;
+!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -176,6 +397,8 @@ if (5 < 10) {
 
 10 == 10;
 10 != 9;
+[1, 2];
+{"foo": "bar"}
 "#;
 
         let test_cases = vec![
@@ -252,6 +475,17 @@ if (5 < 10) {
             NotEq,
             Int(9),
             Semicolon,
+            LBracket,
+            Int(1),
+            Comma,
+            Int(2),
+            RBracket,
+            Semicolon,
+            LBrace,
+            Str(String::from("foo")),
+            Colon,
+            Str(String::from("bar")),
+            RBrace,
             Eof,
         ];
 
@@ -260,7 +494,154 @@ if (5 < 10) {
         for test_case in test_cases {
             let token = lexer.next_token();
 
-            assert_eq!(token, test_case)
+            assert_eq!(token.token, test_case)
+        }
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let x = 10;\n  x != 5\n";
+
+        let expected = vec![
+            (Let, Span::new(0, 3, 1, 1)),
+            (Ident(String::from("x")), Span::new(4, 5, 1, 5)),
+            (Assign, Span::new(6, 7, 1, 7)),
+            (Int(10), Span::new(8, 10, 1, 9)),
+            (Semicolon, Span::new(10, 11, 1, 11)),
+            (Ident(String::from("x")), Span::new(14, 15, 2, 3)),
+            (NotEq, Span::new(16, 18, 2, 5)),
+            (Int(5), Span::new(19, 20, 2, 8)),
+            (Eof, Span::new(21, 21, 3, 1)),
+            (Eof, Span::new(21, 21, 3, 1)),
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for (token, span) in expected {
+            assert_eq!(SpannedToken { token, span }, lexer.next_token());
+        }
+    }
+
+    #[test]
+    fn test_illegal_characters() {
+        // An illegal non-ASCII character is one token, not one per byte.
+        let mut lexer = Lexer::new("é@");
+
+        assert_eq!(SpannedToken { token: Illegal, span: Span::new(0, 2, 1, 1) }, lexer.next_token());
+        assert_eq!(SpannedToken { token: Illegal, span: Span::new(2, 3, 1, 3) }, lexer.next_token());
+        assert_eq!(SpannedToken { token: Eof, span: Span::new(3, 3, 1, 4) }, lexer.next_token());
+    }
+
+    #[test]
+    fn test_embedded_nul() {
+        // Only the end of the input is Eof; a NUL byte inside it is just another illegal character.
+        let mut lexer = Lexer::new("a\0b // c\0d");
+
+        assert_eq!(SpannedToken { token: Ident(String::from("a")), span: Span::new(0, 1, 1, 1) }, lexer.next_token());
+        assert_eq!(SpannedToken { token: Illegal, span: Span::new(1, 2, 1, 2) }, lexer.next_token());
+        assert_eq!(SpannedToken { token: Ident(String::from("b")), span: Span::new(2, 3, 1, 3) }, lexer.next_token());
+        assert_eq!(SpannedToken { token: Eof, span: Span::new(10, 10, 1, 11) }, lexer.next_token());
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let mut lexer = Lexer::new("9223372036854775807 99999999999999999999 -9223372036854775808");
+
+        assert_eq!(SpannedToken { token: Int(i64::MAX), span: Span::new(0, 19, 1, 1) }, lexer.next_token());
+        assert_eq!(SpannedToken { token: Error(LexError::IntegerOverflow), span: Span::new(20, 40, 1, 21) }, lexer.next_token());
+        assert_eq!(Minus, lexer.next_token().token);
+        assert_eq!(SpannedToken { token: Error(LexError::IntegerOverflow), span: Span::new(42, 61, 1, 43) }, lexer.next_token());
+        assert_eq!(Eof, lexer.next_token().token);
+    }
+
+    #[test]
+    fn test_string_literals() {
+        let input = r#""foobar" "foo bar" "" "tab\tnew\nline" "\"quoted\" \\ done" "\u{48}\u{1F600}" "héllo""#;
+
+        let expected = vec![
+            Str(String::from("foobar")),
+            Str(String::from("foo bar")),
+            Str(String::from("")),
+            Str(String::from("tab\tnew\nline")),
+            Str(String::from("\"quoted\" \\ done")),
+            Str(String::from("H\u{1F600}")),
+            Str(String::from("héllo")),
+            Eof,
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for token in expected {
+            assert_eq!(token, lexer.next_token().token);
+        }
+    }
+
+    #[test]
+    fn test_string_literal_errors() {
+        let test_cases = vec![
+            (r#""abc"#, Error(LexError::UnterminatedString), Span::new(0, 4, 1, 1)),
+            (r#""abc\"#, Error(LexError::UnterminatedString), Span::new(0, 5, 1, 1)),
+            (r#""a\qb" 1"#, Error(LexError::InvalidEscape('q')), Span::new(0, 6, 1, 1)),
+            (r#""\u{110000}" 1"#, Error(LexError::InvalidUnicodeEscape), Span::new(0, 12, 1, 1)),
+            (r#""\u41" 1"#, Error(LexError::InvalidUnicodeEscape), Span::new(0, 6, 1, 1)),
+        ];
+
+        for (input, token, span) in test_cases {
+            let mut lexer = Lexer::new(input);
+
+            assert_eq!(SpannedToken { token, span }, lexer.next_token(), "input: {}", input);
+        }
+
+        // Lexing resumes after a literal with a bad escape.
+        let mut lexer = Lexer::new(r#""a\qb" 1"#);
+        lexer.next_token();
+        assert_eq!(Int(1), lexer.next_token().token);
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let input = "let x = 1; // one\n/* two /* nested */ still two */ x / 2 /**/";
+        let mut lexer = Lexer::new(input);
+
+        let mut tokens = vec![];
+        loop {
+            let tok = lexer.next_token();
+            if tok.token == Eof {
+                break;
+            }
+            tokens.push(tok.token);
+        }
+
+        assert_eq!(vec![Let, Ident("x".to_string()), Assign, Int(1), Semicolon, Ident("x".to_string()), Slash, Int(2)], tokens);
+    }
+
+    #[test]
+    fn test_comment_trivia() {
+        let input = "// line\r\n1 /* a\n/* b */ */;";
+        let mut lexer = Lexer::new(input).with_comments();
+
+        let expected = vec![
+            SpannedToken { token: Comment("// line".to_string()), span: Span::new(0, 7, 1, 1) },
+            SpannedToken { token: Int(1), span: Span::new(9, 10, 2, 1) },
+            SpannedToken { token: Comment("/* a\n/* b */ */".to_string()), span: Span::new(11, 26, 2, 3) },
+            SpannedToken { token: Semicolon, span: Span::new(26, 27, 3, 11) },
+            SpannedToken { token: Eof, span: Span::new(27, 27, 3, 12) },
+        ];
+
+        for expected in expected {
+            assert_eq!(expected, lexer.next_token());
+        }
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        for input in ["1 /* open", "1 /* /* nested */", "1 /*/"] {
+            let mut lexer = Lexer::new(input);
+            lexer.next_token();
+
+            let tok = lexer.next_token();
+            assert_eq!(Error(LexError::UnterminatedComment), tok.token, "input: {}", input);
+            assert_eq!(Span::new(2, input.len(), 1, 3), tok.span, "input: {}", input);
         }
     }
 }
@@ -0,0 +1,14 @@
+#[macro_use]
+extern crate lazy_static;
+
+pub mod token;
+pub mod lexer;
+pub mod parser;
+pub mod cst;
+pub mod eval;
+pub mod compiler;
+pub mod vm;
+pub mod diagnostics;
+pub mod formatter;
+pub mod runner;
+pub mod repl;
@@ -1,24 +1,324 @@
-use std::{env, io};
-use std::io::Write;
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::{env, fs, io, process};
 
-#[macro_use]
-extern crate lazy_static;
+use rustic_monkey::compiler::disassembler::disassemble;
+use rustic_monkey::compiler::file;
+use rustic_monkey::eval::environment::Environment;
+use rustic_monkey::runner::RunError;
+use rustic_monkey::vm::Vm;
+use rustic_monkey::{formatter, repl, runner};
 
-mod token;
-mod lexer;
-mod repl;
-mod parser;
+const USAGE: &str = "Usage:
+    rustic-monkey                 start the REPL (or run stdin when it is not a terminal)
+    rustic-monkey <script.mk>     run a script file
+    rustic-monkey -e <code>       run a one-line program
+    rustic-monkey --vm ...        run the script, one-liner or stdin on the bytecode VM instead
+                                  of the tree-walking evaluator
+    rustic-monkey --dump-bytecode ...
+                                  list the bytecode the script, one-liner or stdin compiles to
+                                  instead of running it
+    rustic-monkey compile <script.mk> [-o <script.mkc>]
+                                  compile a script to a bytecode file, by default next to it
+    rustic-monkey run <script.mkc>
+                                  run a compiled bytecode file on the VM
+    rustic-monkey fmt [--check] [<file.mk>...]
+                                  format files in place (or stdin to stdout); with --check,
+                                  list the files that are not formatted instead
+    rustic-monkey -h, --help      show this message";
 
-fn main() {
-    let stdin = io::stdin();
+/// What to do with a program: run it on one of the engines, or list the bytecode it compiles to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Eval,
+    Vm,
+    DumpBytecode,
+}
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Repl,
+    Stdin(Action),
+    File(String, Action),
+    Eval(String, Action),
+    Compile { input: String, output: String },
+    Run(String),
+    Fmt { check: bool, paths: Vec<String> },
+    Help,
+}
+
+fn parse_args(args: &[String], stdin_is_tty: bool) -> Result<Mode, String> {
+    match args {
+        [] if stdin_is_tty => Ok(Mode::Repl),
+        [] => Ok(Mode::Stdin(Action::Eval)),
+        [flag, rest @ ..] if flag == "--vm" || flag == "--dump-bytecode" => {
+            let action = if flag == "--vm" { Action::Vm } else { Action::DumpBytecode };
+            match parse_args(rest, stdin_is_tty)? {
+                Mode::Stdin(Action::Eval) => Ok(Mode::Stdin(action)),
+                Mode::File(path, Action::Eval) => Ok(Mode::File(path, action)),
+                Mode::Eval(code, Action::Eval) => Ok(Mode::Eval(code, action)),
+                _ => Err(format!("`{}` expects a program to run", flag)),
+            }
+        }
+        [flag] if flag == "-h" || flag == "--help" => Ok(Mode::Help),
+        [flag] if flag == "-e" => Err("`-e` expects a program to run".to_string()),
+        [flag, code] if flag == "-e" => Ok(Mode::Eval(code.clone(), Action::Eval)),
+        [command, input] if command == "compile" && !input.starts_with('-') => {
+            let output = Path::new(input).with_extension("mkc").to_string_lossy().into_owned();
+            Ok(Mode::Compile { input: input.clone(), output })
+        }
+        [command, input, flag, output] if command == "compile" && flag == "-o" => {
+            Ok(Mode::Compile { input: input.clone(), output: output.clone() })
+        }
+        [command, ..] if command == "compile" => Err("`compile` expects a script and optionally `-o <output>`".to_string()),
+        [command, path] if command == "run" && !path.starts_with('-') => Ok(Mode::Run(path.clone())),
+        [command, ..] if command == "run" => Err("`run` expects a compiled file to run".to_string()),
+        [command, rest @ ..] if command == "fmt" => {
+            let check = rest.iter().any(|arg| arg == "--check");
+            let paths: Vec<String> = rest.iter().filter(|arg| *arg != "--check").cloned().collect();
+            match paths.iter().find(|path| path.starts_with('-')) {
+                Some(flag) => Err(format!("unrecognised `fmt` flag: {}", flag)),
+                None if check && paths.is_empty() => Err("`fmt --check` expects files to check".to_string()),
+                None => Ok(Mode::Fmt { check, paths }),
+            }
+        }
+        [path] if !path.starts_with('-') => Ok(Mode::File(path.clone(), Action::Eval)),
+        _ => Err(format!("unrecognised arguments: {}", args.join(" "))),
+    }
+}
+
+fn start_repl() -> i32 {
     let stdout = io::stdout();
 
-    let user = env::var("USER").unwrap();
+    match env::var("USER") {
+        Ok(user) => write!(stdout.lock(), "Hello {}! This is the Monkey programming language!\n", user).unwrap(),
+        Err(_) => write!(stdout.lock(), "Hello! This is the Monkey programming language!\n").unwrap(),
+    }
+    write!(stdout.lock(), "Feel free to type in commands, or :help to see what else the REPL can do\n").unwrap();
 
-    write!(stdout.lock(), "Hello {}! This is the Monkey programming language!\n", user).unwrap();
-    write!(stdout.lock(), "Feel free to type in commands\n").unwrap();
+    let options = repl::Options { color: stdout.is_terminal() };
 
-    if let Err(e) = repl::start(stdin.lock(), stdout.lock()) {
+    if let Err(e) = repl::editor::start(&options) {
         eprintln!("Error: {}", e);
+        return 1;
+    }
+
+    0
+}
+
+fn run_source(source: &str, origin: &str, action: Action) -> i32 {
+    let result = match action {
+        Action::Eval => runner::run(source, &Environment::new()).map(|_| ()),
+        Action::Vm => runner::run_vm(source).map(|_| ()),
+        Action::DumpBytecode => {
+            runner::parse(source).and_then(|program| runner::compile(&program)).map(|bytecode| print!("{}", disassemble(&bytecode)))
+        }
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprint!("{}", e.render(source, origin, io::stderr().is_terminal()));
+            e.exit_code()
+        }
+    }
+}
+
+fn compile_file(input: &str, output: &str) -> i32 {
+    let source = match fs::read_to_string(input) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {}", input, e);
+            return runner::EXIT_NO_INPUT;
+        }
+    };
+
+    let bytecode = match runner::parse(&source).and_then(|program| runner::compile(&program)) {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            eprint!("{}", e.render(&source, input, io::stderr().is_terminal()));
+            return e.exit_code();
+        }
+    };
+
+    match fs::write(output, file::encode(&bytecode, input)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: cannot write {}: {}", output, e);
+            runner::EXIT_CANT_CREATE
+        }
+    }
+}
+
+/// Runs a file written by `compile_file`. Errors name the location in the original script, without
+/// quoting it, as the source is not at hand.
+fn run_compiled(path: &str) -> i32 {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {}", path, e);
+            return runner::EXIT_NO_INPUT;
+        }
+    };
+
+    let (bytecode, origin) = match file::decode(&bytes) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: cannot load {}: {}", path, e);
+            return runner::EXIT_PARSE_ERROR;
+        }
+    };
+
+    match Vm::new(bytecode).run() {
+        Ok(_) => 0,
+        Err(e) => {
+            let error = RunError::Runtime(e);
+            eprint!("{}", error.render("", &origin, io::stderr().is_terminal()));
+            error.exit_code()
+        }
+    }
+}
+
+/// Formats each file in place, or with `check` only reports the ones that would change. Returns
+/// the worst exit code: 1 for unformatted files under `check`, or a parse or read error.
+fn format_files(paths: &[String], check: bool) -> i32 {
+    let mut code = 0;
+
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("error: cannot read {}: {}", path, e);
+                code = code.max(runner::EXIT_NO_INPUT);
+                continue;
+            }
+        };
+
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                let error = RunError::Parse(errors);
+                eprint!("{}", error.render(&source, path, io::stderr().is_terminal()));
+                code = code.max(error.exit_code());
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{}", path);
+            code = code.max(1);
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!("error: cannot write {}: {}", path, e);
+            code = code.max(runner::EXIT_CANT_CREATE);
+        }
+    }
+
+    code
+}
+
+fn format_stdin() -> i32 {
+    let mut source = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut source) {
+        eprintln!("error: cannot read stdin: {}", e);
+        return runner::EXIT_NO_INPUT;
+    }
+
+    match formatter::format(&source) {
+        Ok(formatted) => {
+            print!("{}", formatted);
+            0
+        }
+        Err(errors) => {
+            let error = RunError::Parse(errors);
+            eprint!("{}", error.render(&source, "<stdin>", io::stderr().is_terminal()));
+            error.exit_code()
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let code = match parse_args(&args, io::stdin().is_terminal()) {
+        Ok(Mode::Repl) => start_repl(),
+        Ok(Mode::Help) => {
+            println!("{}", USAGE);
+            0
+        }
+        Ok(Mode::Eval(code, engine)) => run_source(&code, "<command line>", engine),
+        Ok(Mode::Compile { input, output }) => compile_file(&input, &output),
+        Ok(Mode::Run(path)) => run_compiled(&path),
+        Ok(Mode::Fmt { paths, .. }) if paths.is_empty() => format_stdin(),
+        Ok(Mode::Fmt { check, paths }) => format_files(&paths, check),
+        Ok(Mode::File(path, engine)) => match fs::read_to_string(&path) {
+            Ok(source) => run_source(&source, &path, engine),
+            Err(e) => {
+                eprintln!("error: cannot read {}: {}", path, e);
+                runner::EXIT_NO_INPUT
+            }
+        },
+        Ok(Mode::Stdin(engine)) => {
+            let mut source = String::new();
+            match io::stdin().read_to_string(&mut source) {
+                Ok(_) => run_source(&source, "<stdin>", engine),
+                Err(e) => {
+                    eprintln!("error: cannot read stdin: {}", e);
+                    runner::EXIT_NO_INPUT
+                }
+            }
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            runner::EXIT_USAGE
+        }
+    };
+
+    process::exit(code);
+}
+
+#[cfg(test)]
+mod main_tests {
+    use crate::{parse_args, Action, Mode};
+
+    #[test]
+    fn test_parse_args() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+
+        assert_eq!(Ok(Mode::Repl), parse_args(&args(&[]), true));
+        assert_eq!(Ok(Mode::Stdin(Action::Eval)), parse_args(&args(&[]), false));
+        assert_eq!(Ok(Mode::File("a.mk".to_string(), Action::Eval)), parse_args(&args(&["a.mk"]), true));
+        assert_eq!(Ok(Mode::Eval("1 + 2".to_string(), Action::Eval)), parse_args(&args(&["-e", "1 + 2"]), false));
+        assert_eq!(Ok(Mode::Help), parse_args(&args(&["--help"]), true));
+        assert!(parse_args(&args(&["-e"]), true).is_err());
+        assert!(parse_args(&args(&["-x"]), true).is_err());
+        assert!(parse_args(&args(&["a.mk", "b.mk"]), true).is_err());
+
+        assert_eq!(Ok(Mode::Stdin(Action::Vm)), parse_args(&args(&["--vm"]), false));
+        assert_eq!(Ok(Mode::File("a.mk".to_string(), Action::Vm)), parse_args(&args(&["--vm", "a.mk"]), true));
+        assert_eq!(Ok(Mode::Eval("1".to_string(), Action::Vm)), parse_args(&args(&["--vm", "-e", "1"]), true));
+        assert!(parse_args(&args(&["--vm"]), true).is_err());
+        assert!(parse_args(&args(&["--vm", "fmt"]), true).is_err());
+
+        assert_eq!(Ok(Mode::File("a.mk".to_string(), Action::DumpBytecode)), parse_args(&args(&["--dump-bytecode", "a.mk"]), true));
+        assert_eq!(Ok(Mode::Eval("1".to_string(), Action::DumpBytecode)), parse_args(&args(&["--dump-bytecode", "-e", "1"]), true));
+        assert!(parse_args(&args(&["--dump-bytecode", "--vm", "a.mk"]), true).is_err());
+
+        let compile = |input: &str, output: &str| Mode::Compile { input: input.to_string(), output: output.to_string() };
+        assert_eq!(Ok(compile("dir/a.mk", "dir/a.mkc")), parse_args(&args(&["compile", "dir/a.mk"]), true));
+        assert_eq!(Ok(compile("a.mk", "out.bin")), parse_args(&args(&["compile", "a.mk", "-o", "out.bin"]), true));
+        assert!(parse_args(&args(&["compile"]), true).is_err());
+        assert!(parse_args(&args(&["compile", "a.mk", "-o"]), true).is_err());
+        assert_eq!(Ok(Mode::Run("a.mkc".to_string())), parse_args(&args(&["run", "a.mkc"]), true));
+        assert!(parse_args(&args(&["run"]), true).is_err());
+
+        let fmt = |check: bool, paths: &[&str]| Mode::Fmt { check, paths: args(paths) };
+        assert_eq!(Ok(fmt(false, &[])), parse_args(&args(&["fmt"]), false));
+        assert_eq!(Ok(fmt(true, &["a.mk", "b.mk"])), parse_args(&args(&["fmt", "a.mk", "--check", "b.mk"]), true));
+        assert!(parse_args(&args(&["fmt", "--check"]), true).is_err());
+        assert!(parse_args(&args(&["fmt", "-w", "a.mk"]), true).is_err());
     }
 }
@@ -1,4 +1,7 @@
-use std::collections::hash_map;
+use std::fmt;
+use std::fmt::Formatter;
+
+use crate::token::Span;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Node {
@@ -14,16 +17,87 @@ pub struct Program {
 
 impl Program {
     pub(crate) fn new() -> Program {
-        let mut program = Program{
+        Program{
             statements: vec![]
-        };
+        }
+    }
+}
+
+// `Display` prints nodes back as canonical Monkey source: every prefix, infix and index
+// expression is wrapped in parentheses so precedence is explicit, e.g. `((-a) * b)`, and every
+// statement ends with `;`. The output parses back to an equal tree.
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let statements: Vec<String> = self.statements.iter().map(|stmt| stmt.to_string()).collect();
+        write!(f, "{}", statements.join("\n"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockStatement {
+    pub statements: Vec<Stmt>,
+}
+
+impl BlockStatement {
+    pub(crate) fn new() -> BlockStatement {
+        BlockStatement{
+            statements: vec![]
+        }
+    }
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.statements.is_empty() {
+            return write!(f, "{{}}");
+        }
+
+        let statements: Vec<String> = self.statements.iter().map(|stmt| stmt.to_string()).collect();
+        write!(f, "{{ {} }}", statements.join(" "))
+    }
+}
+
+// Spans are positional metadata: they are carried on `Stmt`, `Expr` and `Ident` so tooling can
+// map nodes back to source, but they are ignored when comparing nodes.
+
+#[derive(Clone, Debug)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Stmt {
+        Stmt { kind, span }
+    }
+}
+
+impl PartialEq for Stmt {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
 
-        program
+impl From<StmtKind> for Stmt {
+    fn from(kind: StmtKind) -> Self {
+        Stmt::new(kind, Span::default())
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            StmtKind::Let(let_stmt) => write!(f, "let {} = {};", let_stmt.ident, let_stmt.expr),
+            StmtKind::Return(Some(expr)) => write!(f, "return {};", expr),
+            StmtKind::Return(None) => write!(f, "return;"),
+            StmtKind::Expr(expr) => write!(f, "{};", expr),
+        }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Stmt {
+pub enum StmtKind {
     Let(Let),
     Return(Option<Expr>),
     Expr(Expr),
@@ -32,15 +106,103 @@ pub enum Stmt {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Let {
     pub ident: Ident,
+    pub expr: Expr,
+}
 
-    // TODO: Remove Option once expression parsing is done.
-    pub expr: Option<Expr>
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
+    }
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl From<ExprKind> for Expr {
+    fn from(kind: ExprKind) -> Self {
+        Expr::new(kind, Span::default())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Ident(ident) => write!(f, "{}", ident),
+            ExprKind::IntLiteral(value) => write!(f, "{}", value),
+            ExprKind::StringLiteral(value) => write!(f, "\"{}\"", escape(value)),
+            ExprKind::Boolean(value) => write!(f, "{}", value),
+            ExprKind::PrefixExpr { expr, operator } => match operator {
+                Some(operator) => write!(f, "({}{})", operator, expr),
+                None => write!(f, "{}", expr),
+            },
+            ExprKind::InfixExpr { left, right, operator } => write!(f, "({} {} {})", left, operator, right),
+            ExprKind::If { condition, consequence, alternative } => {
+                // Prefix and infix conditions already bring the parentheses `if` requires.
+                match condition.kind {
+                    ExprKind::PrefixExpr { operator: Some(_), .. } | ExprKind::InfixExpr { .. } => {
+                        write!(f, "if {} {}", condition, consequence)?
+                    }
+                    _ => write!(f, "if ({}) {}", condition, consequence)?,
+                }
+                match alternative {
+                    Some(alternative) => write!(f, " else {}", alternative),
+                    None => Ok(()),
+                }
+            }
+            ExprKind::Function { params, body } => {
+                let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                write!(f, "fn({}) {}", params.join(", "), body)
+            }
+            ExprKind::Call { callee, args } => write!(f, "{}({})", callee, join(args)),
+            ExprKind::Array(elements) => write!(f, "[{}]", join(elements)),
+            ExprKind::Index { target, index } => write!(f, "({}[{}])", target, index),
+            ExprKind::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+        }
+    }
+}
+
+fn join(exprs: &[Expr]) -> String {
+    let exprs: Vec<String> = exprs.iter().map(|expr| expr.to_string()).collect();
+    exprs.join(", ")
+}
+
+/// Escapes `value` so the lexer reads it back unchanged.
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        match ch {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+
+    escaped
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Expr {
+pub enum ExprKind {
     Ident(Ident),
     IntLiteral(i64),
+    StringLiteral(String),
+    Boolean(bool),
     PrefixExpr{
         expr: Box<Expr>,
         operator: Option<PrefixOperator>,
@@ -49,7 +211,26 @@ pub enum Expr {
         left: Box<Expr>,
         right: Box<Expr>,
         operator: InfixOperator,
-    }
+    },
+    If{
+        condition: Box<Expr>,
+        consequence: BlockStatement,
+        alternative: Option<BlockStatement>,
+    },
+    Function{
+        params: Vec<Ident>,
+        body: BlockStatement,
+    },
+    Call{
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Array(Vec<Expr>),
+    Index{
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
+    Hash(Vec<(Expr, Expr)>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -58,6 +239,15 @@ pub enum PrefixOperator {
     Negate,
 }
 
+impl fmt::Display for PrefixOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefixOperator::Not => write!(f, "!"),
+            PrefixOperator::Negate => write!(f, "-"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum InfixOperator {
     Add,
@@ -70,9 +260,44 @@ pub enum InfixOperator {
     GreaterThan,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl fmt::Display for InfixOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            InfixOperator::Add => "+",
+            InfixOperator::Sub => "-",
+            InfixOperator::Mul => "*",
+            InfixOperator::Div => "/",
+            InfixOperator::Equals => "==",
+            InfixOperator::NotEquals => "!=",
+            InfixOperator::LessThan => "<",
+            InfixOperator::GreaterThan => ">",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Ident {
-    pub name: String
+    pub name: String,
+    pub span: Span,
+}
+
+impl Ident {
+    pub fn new(name: &str, span: Span) -> Ident {
+        Ident { name: name.to_string(), span }
+    }
+}
+
+impl PartialEq for Ident {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
 }
 
 
@@ -85,5 +310,6 @@ pub enum ExprPrecedence {
     PRODUCT, // *
     PREFIX, // -X or !X
     CALL,
+    INDEX, // array[index]
 }
 
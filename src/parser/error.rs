@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+use crate::token::{Span, Token};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// A specific token (one of `expected`) was required but something else was found.
+    UnexpectedToken {
+        expected: Vec<Token>,
+        found: Token,
+        span: Span,
+    },
+    /// An identifier was required, e.g. after `let` or in a parameter list.
+    ExpectedIdent {
+        found: Token,
+        span: Span,
+    },
+    /// The token cannot start an expression.
+    NoPrefixParseFn {
+        found: Token,
+        span: Span,
+    },
+    /// The token cannot continue an expression.
+    NoInfixParseFn {
+        found: Token,
+        span: Span,
+    },
+    /// The lexer could not read a token, e.g. an unterminated string. `found` is the `Token::Error`.
+    InvalidToken {
+        found: Token,
+        span: Span,
+    },
+    /// Input ended before the `}` closing the block opened at `open`.
+    UnterminatedBlock {
+        open: Span,
+        span: Span,
+    },
+}
+
+impl ParseError {
+    /// Span of the offending token.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::ExpectedIdent { span, .. }
+            | ParseError::NoPrefixParseFn { span, .. }
+            | ParseError::NoInfixParseFn { span, .. }
+            | ParseError::InvalidToken { span, .. }
+            | ParseError::UnterminatedBlock { span, .. } => *span,
+        }
+    }
+
+    /// The token the parser choked on.
+    pub fn found(&self) -> &Token {
+        match self {
+            ParseError::UnexpectedToken { found, .. }
+            | ParseError::ExpectedIdent { found, .. }
+            | ParseError::NoPrefixParseFn { found, .. }
+            | ParseError::NoInfixParseFn { found, .. }
+            | ParseError::InvalidToken { found, .. } => found,
+            ParseError::UnterminatedBlock { .. } => &Token::Eof,
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Eof => "end of input".to_string(),
+        Token::Illegal => "illegal character".to_string(),
+        Token::Error(error) => error.to_string(),
+        other => format!("`{}`", other),
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { expected, found, .. } => {
+                let expected: Vec<String> = expected.iter().map(describe).collect();
+                if expected.len() == 1 {
+                    write!(f, "expected {}, found {}", expected[0], describe(found))
+                } else {
+                    write!(f, "expected one of {}, found {}", expected.join(", "), describe(found))
+                }
+            }
+            ParseError::ExpectedIdent { found, .. } => write!(f, "expected identifier, found {}", describe(found)),
+            ParseError::NoPrefixParseFn { found, .. } => write!(f, "expected expression, found {}", describe(found)),
+            ParseError::NoInfixParseFn { found, .. } => write!(f, "{} is not an infix operator", describe(found)),
+            ParseError::InvalidToken { found, .. } => write!(f, "{}", describe(found)),
+            ParseError::UnterminatedBlock { open, .. } => write!(
+                f,
+                "unterminated block: `{{` opened at {}:{} is never closed",
+                open.line, open.col
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {}
@@ -1,20 +1,26 @@
-mod ast;
+pub mod ast;
+pub mod error;
 
 use std::collections::HashMap;
 
 use ast::InfixOperator;
 
 use crate::lexer::Lexer;
-use crate::parser::ast::{Expr, ExprPrecedence, Ident, Let, PrefixOperator, Program, Stmt};
-use crate::token::Token;
+use crate::parser::ast::{BlockStatement, Expr, ExprKind, ExprPrecedence, Ident, Let, PrefixOperator, Program, Stmt, StmtKind};
+pub use crate::parser::error::ParseError;
+use crate::token::{Span, Token};
 
-type ParseError = String;
 
 pub struct Parser<'a> {
     l: Lexer<'a>,
 
     cur_tok: Token,
     peek_tok: Token,
+
+    cur_span: Span,
+    peek_span: Span,
+
+    errors: Vec<ParseError>,
 }
 
 lazy_static! {
@@ -29,17 +35,31 @@ lazy_static! {
         map.insert(Token::Slash, ExprPrecedence::PRODUCT);
         map.insert(Token::Asterisk, ExprPrecedence::PRODUCT);
         map.insert(Token::LParen, ExprPrecedence::CALL);
+        map.insert(Token::LBracket, ExprPrecedence::INDEX);
         map
     };
 }
 
+/// How tightly `token` binds when it follows an expression.
+pub(crate) fn precedence(token: &Token) -> ExprPrecedence {
+    match PRECEDENCES.get(token) {
+        Some(precedence) => precedence.clone(),
+        None => ExprPrecedence::LOW,
+    }
+}
+
 impl<'a> Parser<'a> {
-    fn new(lexer: Lexer) -> Parser {
+    pub fn new(lexer: Lexer<'a>) -> Parser<'a> {
         let mut parser = Parser {
             l: lexer,
 
             cur_tok: Token::Illegal,
             peek_tok: Token::Illegal,
+
+            cur_span: Span::default(),
+            peek_span: Span::default(),
+
+            errors: vec![],
         };
 
         parser.next_token();
@@ -50,20 +70,66 @@ impl<'a> Parser<'a> {
 
     fn next_token(&mut self) {
         self.cur_tok = self.peek_tok.clone();
-        self.peek_tok = self.l.next_token();
+        self.cur_span = self.peek_span;
+
+        let next = self.l.next_token();
+        self.peek_tok = next.token;
+        self.peek_span = next.span;
     }
 
-    pub fn parse_program(&mut self) -> Result<Program, ParseError> {
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
+        let (program, errors) = self.parse_program_recovering();
+
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the whole input without stopping at the first error. Statements that fail to parse
+    /// are skipped up to the next synchronisation point, so the returned `Program` holds every
+    /// statement that did parse and the error list holds every failure, in source order.
+    pub fn parse_program_recovering(&mut self) -> (Program, Vec<ParseError>) {
         let mut program = Program::new();
 
-        while self.cur_tok != Token::Eof {
-            let stmt = self.parse_stmt()?;
-            program.statements.push(stmt);
+        while !self.curr_tok_is(&Token::Eof) {
+            match self.parse_stmt() {
+                Ok(stmt) => program.statements.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize();
+                }
+            }
 
             self.next_token();
         }
 
-        Ok(program)
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    /// Skips tokens after an error until the parser reaches a point where a new statement can
+    /// start: a `;` (consumed by the caller), a `}` closing the enclosing block, the end of input,
+    /// or just before a `let`/`return` keyword. Blocks opened while skipping are skipped whole.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+
+        loop {
+            match self.cur_tok {
+                Token::Eof => return,
+                Token::LBrace => depth += 1,
+                Token::RBrace if depth == 0 => return,
+                Token::RBrace => depth -= 1,
+                Token::Semicolon if depth == 0 => return,
+                _ => {}
+            }
+
+            if depth == 0 && matches!(self.peek_tok, Token::Let | Token::Return) {
+                return;
+            }
+
+            self.next_token();
+        }
     }
 
     fn parse_stmt(&mut self) -> Result<Stmt, ParseError> {
@@ -76,12 +142,11 @@ impl<'a> Parser<'a> {
 
     fn parse_expression_stmt(&mut self) -> Result<Stmt, ParseError> {
         let expression = self.parse_expression(ExprPrecedence::LOW)?;
+        let span = expression.span;
 
-        if self.peek_tok_is(&Token::Semicolon) {
-            self.next_token();
-        }
+        let span = self.skip_semicolon(span);
 
-        Ok(Stmt::Expr(expression))
+        Ok(Stmt::new(StmtKind::Expr(expression), span))
     }
 
     fn parse_expression(&mut self, precedence: ExprPrecedence) -> Result<Expr, ParseError> {
@@ -97,16 +162,37 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_prefix_expression(&mut self) -> Result<Expr, ParseError> {
+        let span = self.cur_span;
+
         match self.cur_tok.clone() {
             Token::Ident(name) => Ok(self.parse_ident_expr(name.clone())?),
-            Token::Int(value) => Ok(Expr::IntLiteral(value)),
+            Token::Int(value) => Ok(Expr::new(ExprKind::IntLiteral(value), span)),
+            Token::Str(value) => Ok(Expr::new(ExprKind::StringLiteral(value), span)),
+            Token::True => Ok(Expr::new(ExprKind::Boolean(true), span)),
+            Token::False => Ok(Expr::new(ExprKind::Boolean(false), span)),
             Token::Bang => self.parse_bang_expr(),
             Token::Minus => self.parse_negate_expr(),
-            _ => Err(format!("No Prefix parse function registered for {}", self.cur_tok))
+            Token::LParen => self.parse_grouped_expr(),
+            Token::If => self.parse_if_expr(),
+            Token::Function => self.parse_function_expr(),
+            Token::LBracket => self.parse_array_expr(),
+            // Blocks are only parsed where the grammar requires one (after `if`, `else` and `fn(...)`),
+            // so a `{` anywhere an expression may start opens a hash literal.
+            Token::LBrace => self.parse_hash_expr(),
+            Token::Error(error) => Err(ParseError::InvalidToken { found: Token::Error(error), span }),
+            other => Err(ParseError::NoPrefixParseFn { found: other, span }),
         }
     }
 
     fn parse_infix_expression(&mut self, left: Expr) -> Result<Expr, ParseError> {
+        if self.curr_tok_is(&Token::LParen) {
+            return self.parse_call_expr(left);
+        }
+
+        if self.curr_tok_is(&Token::LBracket) {
+            return self.parse_index_expr(left);
+        }
+
         let operator = match self.cur_tok {
             Token::Eq => InfixOperator::Equals,
             Token::NotEq => InfixOperator::NotEquals,
@@ -116,71 +202,280 @@ impl<'a> Parser<'a> {
             Token::Minus => InfixOperator::Sub,
             Token::Slash => InfixOperator::Div,
             Token::Asterisk => InfixOperator::Mul,
-            _ => Err(format!("No Infix parse function registered for {}", self.cur_tok))?
+            _ => return Err(ParseError::NoInfixParseFn { found: self.cur_tok.clone(), span: self.cur_span }),
         };
 
         let precedence = self.curr_precedence();
         self.next_token();
         let right = self.parse_expression(precedence)?;
 
-        Ok(Expr::InfixExpr { left: Box::new(left), right: Box::new(right), operator:  operator})
+        let span = left.span.to(right.span);
+        Ok(Expr::new(ExprKind::InfixExpr { left: Box::new(left), right: Box::new(right), operator:  operator}, span))
 
     }
 
     fn parse_negate_expr(&mut self) -> Result<Expr, ParseError> {
         let operator = PrefixOperator::Negate;
+        let start = self.cur_span;
         self.next_token();
         let right_expression = self.parse_expression(ExprPrecedence::PREFIX)?;
 
-        Ok(Expr::PrefixExpr {
+        let span = start.to(right_expression.span);
+        Ok(Expr::new(ExprKind::PrefixExpr {
             expr: Box::new(right_expression),
             operator: Some(operator),
-        })
+        }, span))
     }
 
     fn parse_bang_expr(&mut self) -> Result<Expr, ParseError> {
         let operator = PrefixOperator::Not;
+        let start = self.cur_span;
         self.next_token();
         let right_expression = self.parse_expression(ExprPrecedence::PREFIX)?;
 
-        Ok(Expr::PrefixExpr {
+        let span = start.to(right_expression.span);
+        Ok(Expr::new(ExprKind::PrefixExpr {
             expr: Box::new(right_expression),
             operator: Some(operator),
-        })
+        }, span))
+    }
+
+    fn parse_grouped_expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.cur_span;
+        self.next_token();
+        let mut expr = self.parse_expression(ExprPrecedence::LOW)?;
+        self.expect_peek(Token::RParen)?;
+
+        expr.span = start.to(self.cur_span);
+        Ok(expr)
+    }
+
+    fn parse_if_expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.cur_span;
+        self.expect_peek(Token::LParen)?;
+        self.next_token();
+        let condition = self.parse_expression(ExprPrecedence::LOW)?;
+        self.expect_peek(Token::RParen)?;
+
+        self.expect_peek(Token::LBrace)?;
+        let consequence = self.parse_block_stmt()?;
+
+        let alternative = if self.peek_tok_is(&Token::Else) {
+            self.next_token();
+            self.expect_peek(Token::LBrace)?;
+            Some(self.parse_block_stmt()?)
+        } else {
+            None
+        };
+
+        Ok(Expr::new(ExprKind::If {
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }, start.to(self.cur_span)))
+    }
+
+    fn parse_block_stmt(&mut self) -> Result<BlockStatement, ParseError> {
+        let mut block = BlockStatement::new();
+        let open = self.cur_span;
+        self.next_token();
+
+        while !self.curr_tok_is(&Token::RBrace) {
+            if self.curr_tok_is(&Token::Eof) {
+                return Err(ParseError::UnterminatedBlock { open, span: self.cur_span });
+            }
+
+            match self.parse_stmt() {
+                Ok(stmt) => block.statements.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize();
+
+                    if self.curr_tok_is(&Token::RBrace) || self.curr_tok_is(&Token::Eof) {
+                        continue;
+                    }
+                }
+            }
+
+            self.next_token();
+        }
+
+        Ok(block)
+    }
+
+    fn parse_function_expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.cur_span;
+        self.expect_peek(Token::LParen)?;
+        let params = self.parse_function_params()?;
+
+        self.expect_peek(Token::LBrace)?;
+        let body = self.parse_block_stmt()?;
+
+        Ok(Expr::new(ExprKind::Function { params, body }, start.to(self.cur_span)))
+    }
+
+    fn parse_function_params(&mut self) -> Result<Vec<Ident>, ParseError> {
+        let mut params = vec![];
+
+        if self.peek_tok_is(&Token::RParen) {
+            self.next_token();
+            return Ok(params);
+        }
+
+        self.next_token();
+        params.push(self.parse_ident()?);
+
+        while self.peek_tok_is(&Token::Comma) {
+            self.next_token();
+            self.next_token();
+            params.push(self.parse_ident()?);
+        }
+
+        self.expect_list_end(Token::RParen)?;
+
+        Ok(params)
+    }
+
+    fn expect_list_end(&mut self, end: Token) -> Result<(), ParseError> {
+        if self.peek_tok_is(&end) {
+            self.next_token();
+            Ok(())
+        } else {
+            Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Comma, end],
+                found: self.peek_tok.clone(),
+                span: self.peek_span,
+            })
+        }
+    }
+
+    fn parse_ident(&mut self) -> Result<Ident, ParseError> {
+        match self.cur_tok.clone() {
+            Token::Ident(name) => Ok(Ident { name, span: self.cur_span }),
+            other => Err(ParseError::ExpectedIdent { found: other, span: self.cur_span }),
+        }
+    }
+
+    fn parse_call_expr(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let args = self.parse_expression_list(Token::RParen)?;
+
+        let span = callee.span.to(self.cur_span);
+        Ok(Expr::new(ExprKind::Call { callee: Box::new(callee), args }, span))
+    }
+
+    fn parse_array_expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.cur_span;
+        let elements = self.parse_expression_list(Token::RBracket)?;
+
+        Ok(Expr::new(ExprKind::Array(elements), start.to(self.cur_span)))
+    }
+
+    fn parse_hash_expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.cur_span;
+        let mut pairs = vec![];
+
+        while !self.peek_tok_is(&Token::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(ExprPrecedence::LOW)?;
+
+            self.expect_peek(Token::Colon)?;
+            self.next_token();
+            let value = self.parse_expression(ExprPrecedence::LOW)?;
+
+            pairs.push((key, value));
+
+            if self.peek_tok_is(&Token::Comma) {
+                self.next_token();
+            } else if !self.peek_tok_is(&Token::RBrace) {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![Token::Comma, Token::RBrace],
+                    found: self.peek_tok.clone(),
+                    span: self.peek_span,
+                });
+            }
+        }
+
+        self.next_token();
+
+        Ok(Expr::new(ExprKind::Hash(pairs), start.to(self.cur_span)))
+    }
+
+    fn parse_index_expr(&mut self, target: Expr) -> Result<Expr, ParseError> {
+        self.next_token();
+        let index = self.parse_expression(ExprPrecedence::LOW)?;
+        self.expect_peek(Token::RBracket)?;
+
+        let span = target.span.to(self.cur_span);
+        Ok(Expr::new(ExprKind::Index { target: Box::new(target), index: Box::new(index) }, span))
+    }
+
+    fn parse_expression_list(&mut self, end: Token) -> Result<Vec<Expr>, ParseError> {
+        let mut list = vec![];
+
+        if self.peek_tok_is(&end) {
+            self.next_token();
+            return Ok(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(ExprPrecedence::LOW)?);
+
+        while self.peek_tok_is(&Token::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(ExprPrecedence::LOW)?);
+        }
+
+        self.expect_list_end(end)?;
+
+        Ok(list)
     }
 
     fn parse_ident_expr(&mut self, ident_name: String) -> Result<Expr, ParseError> {
-        Ok(Expr::Ident(Ident { name: ident_name}))
+        Ok(Expr::new(ExprKind::Ident(Ident { name: ident_name, span: self.cur_span }), self.cur_span))
     }
 
     fn parse_return_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.cur_span;
 
-        // TODO: To parse expression for return stmt once done with parsing them.
-        while !self.curr_tok_is(&Token::Semicolon) {
-            self.next_token();
+        if self.peek_tok_is(&Token::Semicolon) || self.peek_tok_is(&Token::Eof) {
+            let span = self.skip_semicolon(start);
+
+            return Ok(Stmt::new(StmtKind::Return(None), span));
         }
 
+        self.next_token();
+        let expr = self.parse_expression(ExprPrecedence::LOW)?;
+
+        let span = self.skip_semicolon(start.to(expr.span));
 
-        Ok(Stmt::Return(None))
+        Ok(Stmt::new(StmtKind::Return(Some(expr)), span))
     }
 
     fn parse_let_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.cur_span;
         self.next_token();
 
-        let ident = match self.cur_tok.clone() {
-            Token::Ident(name) => Ok(Ident{ name }),
-            other=> Err(format!("Expected Ident token, got {}", other))
-        }?;
+        let ident = self.parse_ident()?;
 
-        self.next_token();
         self.expect_peek(Token::Assign)?;
+        self.next_token();
+
+        let expr = self.parse_expression(ExprPrecedence::LOW)?;
+
+        let span = self.skip_semicolon(start.to(expr.span));
+
+        Ok(Stmt::new(StmtKind::Let(Let{ ident, expr }), span))
+    }
 
-        // TODO: To be wired with Expression parsing once done.
-        while !self.curr_tok_is(&Token::Semicolon) {
+    /// Consumes an optional trailing semicolon, extending `span` over it.
+    fn skip_semicolon(&mut self, span: Span) -> Span {
+        if self.peek_tok_is(&Token::Semicolon) {
             self.next_token();
+            span.to(self.cur_span)
+        } else {
+            span
         }
-
-        Ok(Stmt::Let(Let{ ident, expr: None}))
     }
 
     fn curr_tok_is(&self, tok: &Token) -> bool {
@@ -192,35 +487,33 @@ impl<'a> Parser<'a> {
     }
 
     fn peek_precedence(&self) -> ExprPrecedence {
-        match PRECEDENCES.get(&self.peek_tok) {
-            Some(precedence) => precedence.clone(),
-            None => ExprPrecedence::LOW,
-        }
+        precedence(&self.peek_tok)
     }
 
     fn curr_precedence(&self) -> ExprPrecedence {
-        match PRECEDENCES.get(&self.cur_tok) {
-            Some(precedence) => precedence.clone(),
-            None => ExprPrecedence::LOW,
-        }
+        precedence(&self.cur_tok)
     }
 
     fn expect_peek(&mut self, tok: Token) -> Result<(), ParseError>{
-        return if self.cur_tok == tok {
+        return if self.peek_tok == tok {
             self.next_token();
             Ok(())
         } else {
-            let e = format!("expected peek token: {} got: {}", tok, self.cur_tok.clone());
-            Err(e)
+            Err(ParseError::UnexpectedToken {
+                expected: vec![tok],
+                found: self.peek_tok.clone(),
+                span: self.peek_span,
+            })
         };
     }
 }
 
 #[cfg(test)]
 mod parser_tests {
-    use crate::lexer::Lexer;
-    use crate::parser::ast::{Expr, PrefixOperator, Stmt};
-    use crate::parser::Parser;
+    use crate::lexer::{LexError, Lexer};
+    use crate::parser::ast::{BlockStatement, Expr, ExprKind, Ident, Let, PrefixOperator, Stmt, StmtKind};
+    use crate::parser::{ParseError, Parser};
+    use crate::token::{Span, Token};
 
     use super::ast::InfixOperator;
 
@@ -236,17 +529,56 @@ mod parser_tests {
 
         let program = parser.parse_program();
 
-        assert_eq!(false, program.is_err(), "Error occurred while parsing, got error: {}", program.err().unwrap());
+        assert_eq!(false, program.is_err(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
 
         let program = program.unwrap();
         assert_eq!(program.statements.len(), 3);
 
         let expected_idents = ["x", "y", "foo"];
+        let expected_values: [i64; 3] = [10, 100, 838383];
         for (idx, stmt) in program.statements.iter().enumerate() {
-            assert!(matches!(stmt, Stmt::Let(let_stmt) if let_stmt.ident.name == expected_idents[idx]));
+            assert!(matches!(&stmt.kind, StmtKind::Let(let_stmt) if let_stmt.ident.name == expected_idents[idx]
+                && matches!(let_stmt.expr.kind, ExprKind::IntLiteral(value) if value == expected_values[idx])));
         }
     }
 
+    #[test]
+    fn test_let_statement_expressions() {
+        let input = "let x = a + 5 * b; let y = -x";
+
+        let l = Lexer::new(input);
+        let mut parser = Parser::new(l);
+
+        let program = parser.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+        let expected = vec![
+            Stmt::from(StmtKind::Let(Let {
+                ident: Ident { name: "x".to_string(), span: Span::default() },
+                expr: Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(Expr::from(ExprKind::Ident(Ident { name: "a".to_string(), span: Span::default() }))),
+                    operator: InfixOperator::Add,
+                    right: Box::new(Expr::from(ExprKind::InfixExpr {
+                        left: Box::new(Expr::from(ExprKind::IntLiteral(5))),
+                        operator: InfixOperator::Mul,
+                        right: Box::new(Expr::from(ExprKind::Ident(Ident { name: "b".to_string(), span: Span::default() }))),
+                    })),
+                }),
+            })),
+            Stmt::from(StmtKind::Let(Let {
+                ident: Ident { name: "y".to_string(), span: Span::default() },
+                expr: Expr::from(ExprKind::PrefixExpr {
+                    expr: Box::new(Expr::from(ExprKind::Ident(Ident { name: "x".to_string(), span: Span::default() }))),
+                    operator: Some(PrefixOperator::Negate),
+                }),
+            })),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
     #[test]
     fn test_return_statement() {
         let input = r#"
@@ -265,11 +597,37 @@ return 993322;
         let program = program.unwrap();
         assert_eq!(3, program.statements.len());
 
-        for (_, stmt) in program.statements.iter().enumerate() {
-            assert!(matches!(stmt, Stmt::Return(_)))
+        let expected_values: [i64; 3] = [5, 10, 993322];
+        for (idx, stmt) in program.statements.iter().enumerate() {
+            assert!(matches!(&stmt.kind, StmtKind::Return(Some(expr))
+                if matches!(expr.kind, ExprKind::IntLiteral(value) if value == expected_values[idx])))
         }
     }
 
+    #[test]
+    fn test_return_statement_expressions() {
+        let input = "return;\nreturn x != 5";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+        let expected = vec![
+            Stmt::from(StmtKind::Return(None)),
+            Stmt::from(StmtKind::Return(Some(Expr::from(ExprKind::InfixExpr {
+                left: Box::new(Expr::from(ExprKind::Ident(Ident { name: "x".to_string(), span: Span::default() }))),
+                operator: InfixOperator::NotEquals,
+                right: Box::new(Expr::from(ExprKind::IntLiteral(5))),
+            })))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
     #[test]
     fn test_identifier_expressions() {
         let input = r#"foobar; gmail;"#;
@@ -279,7 +637,7 @@ return 993322;
 
         let program = p.parse_program();
 
-        assert_eq!(false, program.is_err(), "Error occurred while parsing, got error: {}", program.err().unwrap());
+        assert_eq!(false, program.is_err(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
 
         let program = program.unwrap();
 
@@ -287,8 +645,8 @@ return 993322;
         assert_eq!(expected_idents.len(), program.statements.len());
 
         for (idx, statement) in program.statements.iter().enumerate() {
-            assert!(matches!(statement, Stmt::Expr(expr) if
-                                                        matches!(&expr, Expr::Ident(ident) if
+            assert!(matches!(&statement.kind, StmtKind::Expr(expr) if
+                                                        matches!(&expr.kind, ExprKind::Ident(ident) if
                                                                     *ident.name == expected_idents[idx].to_string())));
         }
     }
@@ -302,7 +660,7 @@ return 993322;
 
         let program = p.parse_program();
 
-        assert_eq!(false, program.is_err(), "Error occurred while parsing, got error: {}", program.err().unwrap());
+        assert_eq!(false, program.is_err(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
 
         let program = program.unwrap();
 
@@ -310,8 +668,8 @@ return 993322;
         assert_eq!(expected_ints.len(), program.statements.len());
 
         for (idx, statement) in program.statements.iter().enumerate() {
-            assert!(matches!(statement, Stmt::Expr(expr) if
-                                                        matches!(&expr, Expr::IntLiteral(number) if
+            assert!(matches!(&statement.kind, StmtKind::Expr(expr) if
+                                                        matches!(&expr.kind, ExprKind::IntLiteral(number) if
                                                                     *number == expected_ints[idx])));
         }
     }
@@ -335,7 +693,7 @@ return 993322;
 
             let program = p.parse_program();
 
-            assert_eq!(false, program.is_err(), "Error occurred while parsing, got error: {}", program.err().unwrap());
+            assert_eq!(false, program.is_err(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
 
             let program = program.unwrap();
 
@@ -343,11 +701,11 @@ return 993322;
 
 
             assert!(
-                matches!(program.statements[0].clone(), Stmt::Expr(expr)
-                    if matches!(&expr, Expr::PrefixExpr { expr: prefix_expr, operator: oper }
+                matches!(program.statements[0].clone().kind, StmtKind::Expr(expr)
+                    if matches!(&expr.kind, ExprKind::PrefixExpr { expr: prefix_expr, operator: oper }
                         if matches!(oper, Some(operator)
                             if *operator == test_case.operator)
-                        && matches!(**(prefix_expr), Expr::IntLiteral(literal_value)
+                        && matches!(prefix_expr.kind, ExprKind::IntLiteral(literal_value)
                             if (literal_value == test_case.literal_value))
                     )
                 )
@@ -386,11 +744,11 @@ return 993322;
             dbg!(&program);
 
             let stmt = program.statements[0].clone();
-            assert!(matches!(stmt, Stmt::Expr(expr) if
-                matches!(&expr, Expr::InfixExpr { left: left_expr, operator: oper, right: right_expr }
-                    if matches!(**left_expr, Expr::IntLiteral(left_value)
+            assert!(matches!(stmt.kind, StmtKind::Expr(expr) if
+                matches!(&expr.kind, ExprKind::InfixExpr { left: left_expr, operator: oper, right: right_expr }
+                    if matches!(left_expr.kind, ExprKind::IntLiteral(left_value)
                         if left_value == test_case.left_value)
-                    && matches!(**right_expr, Expr::IntLiteral(right_value)
+                    && matches!(right_expr.kind, ExprKind::IntLiteral(right_value)
                         if right_value == test_case.right_value)
                     && *oper == test_case.operator
                 )
@@ -398,4 +756,532 @@ return 993322;
             dbg!(&"Passed");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_string_literal_expressions() {
+        let input = r#""hello world"; "a" + "b""#;
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::StringLiteral("hello world".to_string())))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::InfixExpr {
+                left: Box::new(Expr::from(ExprKind::StringLiteral("a".to_string()))),
+                operator: InfixOperator::Add,
+                right: Box::new(Expr::from(ExprKind::StringLiteral("b".to_string()))),
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_array_and_index_expressions() {
+        let input = "[]; [1, 2 * 2, a]; myArray[1 + 1]; a * [1, 2][0]; f(x)[0]; xs[0](1)";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+
+        let int = |value: i64| Expr::from(ExprKind::IntLiteral(value));
+        let ident = |name: &str| Expr::from(ExprKind::Ident(Ident { name: name.to_string(), span: Span::default() }));
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Array(vec![])))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Array(vec![
+                int(1),
+                Expr::from(ExprKind::InfixExpr { left: Box::new(int(2)), operator: InfixOperator::Mul, right: Box::new(int(2)) }),
+                ident("a"),
+            ])))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Index {
+                target: Box::new(ident("myArray")),
+                index: Box::new(Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(int(1)),
+                    operator: InfixOperator::Add,
+                    right: Box::new(int(1)),
+                })),
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::InfixExpr {
+                left: Box::new(ident("a")),
+                operator: InfixOperator::Mul,
+                right: Box::new(Expr::from(ExprKind::Index {
+                    target: Box::new(Expr::from(ExprKind::Array(vec![int(1), int(2)]))),
+                    index: Box::new(int(0)),
+                })),
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Index {
+                target: Box::new(Expr::from(ExprKind::Call { callee: Box::new(ident("f")), args: vec![ident("x")] })),
+                index: Box::new(int(0)),
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Call {
+                callee: Box::new(Expr::from(ExprKind::Index { target: Box::new(ident("xs")), index: Box::new(int(0)) })),
+                args: vec![int(1)],
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_hash_literal_expressions() {
+        let input = r#"{}; {"one": 1, true: 2 + 3, 4: x,}; if (x) { {"a": 1} }"#;
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+
+        let int = |value: i64| Expr::from(ExprKind::IntLiteral(value));
+        let string = |value: &str| Expr::from(ExprKind::StringLiteral(value.to_string()));
+        let ident = |name: &str| Expr::from(ExprKind::Ident(Ident { name: name.to_string(), span: Span::default() }));
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Hash(vec![])))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Hash(vec![
+                (string("one"), int(1)),
+                (Expr::from(ExprKind::Boolean(true)), Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(int(2)),
+                    operator: InfixOperator::Add,
+                    right: Box::new(int(3)),
+                })),
+                (int(4), ident("x")),
+            ])))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::If {
+                condition: Box::new(ident("x")),
+                consequence: BlockStatement {
+                    statements: vec![Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Hash(vec![(string("a"), int(1))]))))],
+                },
+                alternative: None,
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        let input = "true; false; 3 > 5 == false";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Boolean(true)))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Boolean(false)))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::InfixExpr {
+                left: Box::new(Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(Expr::from(ExprKind::IntLiteral(3))),
+                    operator: InfixOperator::GreaterThan,
+                    right: Box::new(Expr::from(ExprKind::IntLiteral(5))),
+                })),
+                operator: InfixOperator::Equals,
+                right: Box::new(Expr::from(ExprKind::Boolean(false))),
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_grouped_expressions() {
+        let input = "(1 + 2) * 3; -(5 + 5)";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::InfixExpr {
+                left: Box::new(Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(Expr::from(ExprKind::IntLiteral(1))),
+                    operator: InfixOperator::Add,
+                    right: Box::new(Expr::from(ExprKind::IntLiteral(2))),
+                })),
+                operator: InfixOperator::Mul,
+                right: Box::new(Expr::from(ExprKind::IntLiteral(3))),
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::PrefixExpr {
+                expr: Box::new(Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(Expr::from(ExprKind::IntLiteral(5))),
+                    operator: InfixOperator::Add,
+                    right: Box::new(Expr::from(ExprKind::IntLiteral(5))),
+                })),
+                operator: Some(PrefixOperator::Negate),
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let input = "if (x < y) { x } if (x < y) { x } else { y; }";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+
+        let ident = |name: &str| Expr::from(ExprKind::Ident(Ident { name: name.to_string(), span: Span::default() }));
+        let condition = Box::new(Expr::from(ExprKind::InfixExpr {
+            left: Box::new(ident("x")),
+            operator: InfixOperator::LessThan,
+            right: Box::new(ident("y")),
+        }));
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::If {
+                condition: condition.clone(),
+                consequence: BlockStatement { statements: vec![Stmt::from(StmtKind::Expr(ident("x")))] },
+                alternative: None,
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::If {
+                condition,
+                consequence: BlockStatement { statements: vec![Stmt::from(StmtKind::Expr(ident("x")))] },
+                alternative: Some(BlockStatement { statements: vec![Stmt::from(StmtKind::Expr(ident("y")))] }),
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let test_cases: Vec<(&str, ParseError)> = vec![
+            ("(1 + 2", ParseError::UnexpectedToken {
+                expected: vec![Token::RParen],
+                found: Token::Eof,
+                span: Span::new(6, 6, 1, 7),
+            }),
+            ("if (x) { x", ParseError::UnterminatedBlock {
+                open: Span::new(7, 8, 1, 8),
+                span: Span::new(10, 10, 1, 11),
+            }),
+            ("if x { x }", ParseError::UnexpectedToken {
+                expected: vec![Token::LParen],
+                found: Token::Ident("x".to_string()),
+                span: Span::new(3, 4, 1, 4),
+            }),
+            ("let = 5;", ParseError::ExpectedIdent { found: Token::Assign, span: Span::new(4, 5, 1, 5) }),
+            ("fn(x, 1) { x }", ParseError::ExpectedIdent { found: Token::Int(1), span: Span::new(6, 7, 1, 7) }),
+            ("add(1,\n  2 3)", ParseError::UnexpectedToken {
+                expected: vec![Token::Comma, Token::RParen],
+                found: Token::Int(3),
+                span: Span::new(11, 12, 2, 5),
+            }),
+            ("fn(x) x", ParseError::UnexpectedToken {
+                expected: vec![Token::LBrace],
+                found: Token::Ident("x".to_string()),
+                span: Span::new(6, 7, 1, 7),
+            }),
+            ("[1, 2", ParseError::UnexpectedToken {
+                expected: vec![Token::Comma, Token::RBracket],
+                found: Token::Eof,
+                span: Span::new(5, 5, 1, 6),
+            }),
+            ("{\"a\" 1}", ParseError::UnexpectedToken {
+                expected: vec![Token::Colon],
+                found: Token::Int(1),
+                span: Span::new(5, 6, 1, 6),
+            }),
+            ("{1: 2 3: 4}", ParseError::UnexpectedToken {
+                expected: vec![Token::Comma, Token::RBrace],
+                found: Token::Int(3),
+                span: Span::new(6, 7, 1, 7),
+            }),
+            ("let x = ;", ParseError::NoPrefixParseFn { found: Token::Semicolon, span: Span::new(8, 9, 1, 9) }),
+            ("let s = \"abc", ParseError::InvalidToken {
+                found: Token::Error(LexError::UnterminatedString),
+                span: Span::new(8, 12, 1, 9),
+            }),
+            ("let n = 99999999999999999999;", ParseError::InvalidToken {
+                found: Token::Error(LexError::IntegerOverflow),
+                span: Span::new(8, 28, 1, 9),
+            }),
+        ];
+
+        for (input, expected) in test_cases {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            assert_eq!(Err(vec![expected]), p.parse_program(), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_messages() {
+        let test_cases: Vec<(&str, &str)> = vec![
+            ("(1 + 2", "expected `)`, found end of input"),
+            ("add(1, 2;", "expected one of `,`, `)`, found `;`"),
+            ("let 5 = 5;", "expected identifier, found `5`"),
+            ("let x = );", "expected expression, found `)`"),
+            ("if (x) {\n x", "unterminated block: `{` opened at 1:8 is never closed"),
+            ("\"abc", "unterminated string literal"),
+            ("\"a\\qb\"", "unknown escape sequence `\\q`"),
+        ];
+
+        for (input, expected) in test_cases {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            let errors = p.parse_program().expect_err(input);
+            assert_eq!(expected, errors[0].to_string());
+        }
+    }
+
+    #[test]
+    fn test_function_literal_parsing() {
+        let input = "fn() {}; fn(x) { x }; fn(x, y, z) { x + y; }";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+
+        let ident = |name: &str| Ident { name: name.to_string(), span: Span::default() };
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Function { params: vec![], body: BlockStatement { statements: vec![] } }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Function {
+                params: vec![ident("x")],
+                body: BlockStatement { statements: vec![Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Ident(ident("x")))))] },
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Function {
+                params: vec![ident("x"), ident("y"), ident("z")],
+                body: BlockStatement {
+                    statements: vec![Stmt::from(StmtKind::Expr(Expr::from(ExprKind::InfixExpr {
+                        left: Box::new(Expr::from(ExprKind::Ident(ident("x")))),
+                        operator: InfixOperator::Add,
+                        right: Box::new(Expr::from(ExprKind::Ident(ident("y")))),
+                    })))],
+                },
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_call_expression_parsing() {
+        let input = "add(1, mul(2, 3)); noop(); fn(x){x}(5); a + b(c) * d";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert!(program.is_ok(), "Error occurred while parsing, got errors: {:?}", program.err().unwrap());
+
+        let program = program.unwrap();
+
+        let ident = |name: &str| Expr::from(ExprKind::Ident(Ident { name: name.to_string(), span: Span::default() }));
+        let expected = vec![
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Call {
+                callee: Box::new(ident("add")),
+                args: vec![
+                    Expr::from(ExprKind::IntLiteral(1)),
+                    Expr::from(ExprKind::Call {
+                        callee: Box::new(ident("mul")),
+                        args: vec![Expr::from(ExprKind::IntLiteral(2)), Expr::from(ExprKind::IntLiteral(3))],
+                    }),
+                ],
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Call { callee: Box::new(ident("noop")), args: vec![] }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::Call {
+                callee: Box::new(Expr::from(ExprKind::Function {
+                    params: vec![Ident { name: "x".to_string(), span: Span::default() }],
+                    body: BlockStatement { statements: vec![Stmt::from(StmtKind::Expr(ident("x")))] },
+                })),
+                args: vec![Expr::from(ExprKind::IntLiteral(5))],
+            }))),
+            Stmt::from(StmtKind::Expr(Expr::from(ExprKind::InfixExpr {
+                left: Box::new(ident("a")),
+                operator: InfixOperator::Add,
+                right: Box::new(Expr::from(ExprKind::InfixExpr {
+                    left: Box::new(Expr::from(ExprKind::Call { callee: Box::new(ident("b")), args: vec![ident("c")] })),
+                    operator: InfixOperator::Mul,
+                    right: Box::new(ident("d")),
+                })),
+            }))),
+        ];
+
+        assert_eq!(expected, program.statements);
+    }
+
+    #[test]
+    fn test_operator_precedence_parsing() {
+        let tests = vec![
+            ("-a * b", "((-a) * b);"),
+            ("!-a", "(!(-a));"),
+            ("a + b + c", "((a + b) + c);"),
+            ("a + b - c", "((a + b) - c);"),
+            ("a * b * c", "((a * b) * c);"),
+            ("a * b / c", "((a * b) / c);"),
+            ("a + b / c", "(a + (b / c));"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f);"),
+            ("3 + 4; -5 * 5", "(3 + 4);\n((-5) * 5);"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4));"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4));"),
+            ("3 + 4 * 5 == 3 * 1 + 4 * 5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)));"),
+            ("true", "true;"),
+            ("3 > 5 == false", "((3 > 5) == false);"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4);"),
+            ("(5 + 5) * 2", "((5 + 5) * 2);"),
+            ("2 / (5 + 5)", "(2 / (5 + 5));"),
+            ("-(5 + 5)", "(-(5 + 5));"),
+            ("!(true == true)", "(!(true == true));"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d);"),
+            ("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)));"),
+            ("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g));"),
+            ("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d);"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])));"),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(Lexer::new(input)).parse_program().expect(input);
+            assert_eq!(expected, program.to_string(), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let tests = vec![
+            ("let x = 5", "let x = 5;"),
+            ("return;", "return;"),
+            ("return x", "return x;"),
+            ("if (x < y) { x } else { y; z }", "if (x < y) { x; } else { y; z; };"),
+            ("if (x) { }", "if (x) {};"),
+            ("let f = fn(a, b) { return a; }", "let f = fn(a, b) { return a; };"),
+            ("fn() { 1 }()", "fn() { 1; }();"),
+            (r#"{"a\tb": 1, true: [], 2: {}}"#, r#"{"a\tb": 1, true: [], 2: {}};"#),
+            (r#""say \"hi\"\n\\ \u{7}""#, r#""say \"hi\"\n\\ \u{7}";"#),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(Lexer::new(input)).parse_program().expect(input);
+            assert_eq!(expected, program.to_string(), "input: {}", input);
+
+            let reparsed = Parser::new(Lexer::new(expected)).parse_program().expect(expected);
+            assert_eq!(program, reparsed, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_node_spans() {
+        let input = "let x = -a * (b + 1);\nfoo(1, 2)";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program().unwrap();
+
+        let let_stmt = &program.statements[0];
+        assert_eq!(Span::new(0, 21, 1, 1), let_stmt.span);
+
+        let (ident, expr) = match &let_stmt.kind {
+            StmtKind::Let(Let { ident, expr }) => (ident, expr),
+            other => panic!("expected let statement, got {:?}", other),
+        };
+        assert_eq!(Span::new(4, 5, 1, 5), ident.span);
+        assert_eq!(Span::new(8, 20, 1, 9), expr.span);
+
+        let (left, right) = match &expr.kind {
+            ExprKind::InfixExpr { left, right, .. } => (left, right),
+            other => panic!("expected infix expression, got {:?}", other),
+        };
+        assert_eq!(Span::new(8, 10, 1, 9), left.span);
+        assert_eq!(Span::new(13, 20, 1, 14), right.span);
+
+        let call_stmt = &program.statements[1];
+        assert_eq!(Span::new(22, 31, 2, 1), call_stmt.span);
+        assert!(matches!(&call_stmt.kind, StmtKind::Expr(call) if call.span == call_stmt.span
+            && matches!(&call.kind, ExprKind::Call { args, .. } if args[1].span == Span::new(29, 30, 2, 8))));
+    }
+
+    #[test]
+    fn test_parse_error_recovery() {
+        let input = r#"
+let x 5;
+let = 10;
+let y = 3;
+let add = fn(a, b) {
+    let z = ;
+    a + b
+};
+add(1 2);
+y
+"#;
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let (program, errors) = p.parse_program_recovering();
+
+        let expected_errors = vec![
+            ParseError::UnexpectedToken { expected: vec![Token::Assign], found: Token::Int(5), span: Span::new(7, 8, 2, 7) },
+            ParseError::ExpectedIdent { found: Token::Assign, span: Span::new(14, 15, 3, 5) },
+            ParseError::NoPrefixParseFn { found: Token::Semicolon, span: Span::new(64, 65, 6, 13) },
+            ParseError::UnexpectedToken {
+                expected: vec![Token::Comma, Token::RParen],
+                found: Token::Int(2),
+                span: Span::new(85, 86, 9, 7),
+            },
+        ];
+        assert_eq!(expected_errors, errors);
+
+        let parsed: Vec<String> = program.statements.iter().map(|stmt| match &stmt.kind {
+            StmtKind::Let(let_stmt) => format!("let {}", let_stmt.ident.name),
+            StmtKind::Return(_) => "return".to_string(),
+            StmtKind::Expr(_) => "expr".to_string(),
+        }).collect();
+        assert_eq!(vec!["let y", "let add", "expr"], parsed);
+
+        let add_body_len = match &program.statements[1].kind {
+            StmtKind::Let(Let { expr, .. }) => match &expr.kind {
+                ExprKind::Function { body, .. } => body.statements.len(),
+                other => panic!("expected function literal, got {:?}", other),
+            },
+            other => panic!("expected let statement, got {:?}", other),
+        };
+        assert_eq!(1, add_body_len);
+    }
+
+    #[test]
+    fn test_parse_program_reports_every_error() {
+        let input = "let = 1; let x = ); if (x { 1 }; let y = 2";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let errors = p.parse_program().expect_err(input);
+        assert_eq!(3, errors.len(), "errors: {:?}", errors);
+    }
+}
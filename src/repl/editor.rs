@@ -0,0 +1,144 @@
+use std::io;
+use std::path::PathBuf;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::eval::builtins::BUILTINS;
+use crate::eval::environment::Env;
+use crate::lexer::KEYWORDS;
+use crate::repl::{Options, Session, COMMANDS};
+
+const HISTORY_FILE: &str = ".monkey_history";
+
+/// Runs the REPL on the terminal with line editing, history persisted to `~/.monkey_history` and
+/// tab completion. Ctrl-C clears the current input and Ctrl-D ends the session.
+pub fn start(options: &Options) -> Result<(), ReadlineError> {
+    let mut session = Session::new(options.clone());
+    let mut editor: Editor<MonkeyHelper, _> = Editor::new()?;
+    editor.set_helper(Some(MonkeyHelper { env: session.env().clone() }));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session.
+        let _ = editor.load_history(path);
+    }
+
+    let mut output = io::stdout();
+
+    let result = loop {
+        match editor.readline(session.prompt()) {
+            Ok(mut line) => {
+                editor.add_history_entry(line.as_str())?;
+                line.push('\n');
+                session.feed(&line, &mut output)?;
+
+                // `:reset` swaps the environment out, so keep completion looking at the live one.
+                if let Some(helper) = editor.helper_mut() {
+                    helper.env = session.env().clone();
+                }
+            }
+            Err(ReadlineError::Interrupted) => session.cancel(),
+            Err(ReadlineError::Eof) => break session.finish(&mut output).map_err(ReadlineError::from),
+            Err(e) => break Err(e),
+        }
+    };
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+
+    result
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join(HISTORY_FILE))
+}
+
+struct MonkeyHelper {
+    env: Env,
+}
+
+impl Completer for MonkeyHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.env))
+    }
+}
+
+impl Hinter for MonkeyHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MonkeyHelper {}
+
+impl Validator for MonkeyHelper {}
+
+impl Helper for MonkeyHelper {}
+
+/// Completes the word ending at `pos`: a command name right after a leading `:`, otherwise a
+/// keyword, builtin or name bound in `env`. Returns where the word starts and the candidates.
+fn complete(line: &str, pos: usize, env: &Env) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| ch.is_ascii_alphanumeric() || *ch == '_')
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let prefix = &before[start..];
+
+    let mut candidates: Vec<String> = if before[..start].trim_start() == ":" {
+        COMMANDS.iter().map(|command| command.to_string()).collect()
+    } else {
+        KEYWORDS
+            .keys()
+            .cloned()
+            .chain(BUILTINS.iter().map(|builtin| builtin.name.to_string()))
+            .chain(env.borrow().bindings().into_iter().map(|(name, _)| name))
+            .collect()
+    };
+
+    candidates.retain(|candidate| candidate.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod editor_tests {
+    use crate::eval::environment::Environment;
+    use crate::eval::object::Object;
+    use crate::repl::editor::complete;
+
+    #[test]
+    fn test_complete() {
+        let env = Environment::new();
+        env.borrow_mut().set("length", Object::Integer(1));
+        env.borrow_mut().set("fib", Object::Null);
+
+        let tests = vec![
+            ("le", 2, (0, vec!["len", "length", "let"])),
+            ("let x = f", 9, (8, vec!["false", "fib", "first", "fn"])),
+            ("puts(re)", 7, (5, vec!["rest", "return"])),
+            (":ti", 3, (1, vec!["time"])),
+            (":load re", 8, (6, vec!["rest", "return"])),
+        ];
+
+        for (line, pos, (start, candidates)) in tests {
+            let expected: Vec<String> = candidates.iter().map(|c| c.to_string()).collect();
+            assert_eq!((start, expected), complete(line, pos, &env), "line: {:?}", line);
+        }
+
+        // An empty prefix offers everything.
+        let (start, candidates) = complete("1 + ", 4, &env);
+        assert_eq!(4, start);
+        assert_eq!(7 + 6 + 2, candidates.len());
+    }
+}
@@ -0,0 +1,392 @@
+pub mod editor;
+
+use std::io::{BufRead, Write};
+use std::time::Instant;
+use std::{fs, io};
+
+use crate::compiler::disassembler::disassemble;
+use crate::compiler::symbol_table::SymbolTable;
+use crate::compiler::{Bytecode, Compiler};
+use crate::diagnostics::{render, Diagnostic};
+use crate::eval::builtins::capture_output;
+use crate::eval::environment::{Env, Environment};
+use crate::lexer::{LexError, Lexer};
+use crate::parser::ast::StmtKind;
+use crate::runner;
+use crate::runner::RunError;
+use crate::token::Token;
+
+const ORIGIN: &str = "<repl>";
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+/// Names of the `:` commands, for completion.
+const COMMANDS: &[&str] = &["tokens", "ast", "bytecode", "env", "reset", "load", "time", "help"];
+
+const HELP: &str = "Commands:
+    :tokens <code>   print the tokens the lexer produces for <code>
+    :ast <code>      print the syntax tree the parser produces for <code>
+    :bytecode <code> print the bytecode <code> compiles to
+    :env             list the bindings made in this session
+    :reset           forget every binding
+    :load <file>     run a script file in this session
+    :time <code>     evaluate <code> and report how long it took
+    :help            show this message
+
+Unfinished input continues on the next line after a `..` prompt; two blank lines discard it.";
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Render diagnostics with ANSI colors.
+    pub color: bool,
+}
+
+pub fn start<R: BufRead, W: Write>(input: R, output: W) -> io::Result<()> {
+    start_with_options(input, output, &Options::default())
+}
+
+/// Reads, evaluates and prints one statement at a time from `input`. This is the plain frontend
+/// used for tests and non-interactive input; see `editor::start` for the line-editing one.
+pub fn start_with_options<R: BufRead, W: Write>(mut input: R, mut output: W, options: &Options) -> io::Result<()> {
+    let mut session = Session::new(options.clone());
+    let mut line = String::new();
+
+    loop {
+        write!(output, "{}", session.prompt())?;
+        output.flush()?;
+
+        line.clear();
+        input.read_line(&mut line)?;
+
+        if line.is_empty() {
+            return session.finish(&mut output);
+        }
+
+        session.feed(&line, &mut output)?;
+    }
+}
+
+/// The state of a REPL session, independent of where its lines come from. Bindings persist across
+/// inputs, and errors are rendered without ending the session. Lines starting with `:` are
+/// commands, see `HELP`.
+///
+/// Input that stops partway through a statement (see `is_incomplete`) is buffered behind a `.. `
+/// prompt until it is complete. Two blank lines in a row discard the buffer.
+pub struct Session {
+    env: Env,
+    buffer: String,
+    last_was_blank: bool,
+    options: Options,
+}
+
+impl Session {
+    pub fn new(options: Options) -> Session {
+        Session { env: Environment::new(), buffer: String::new(), last_was_blank: false, options }
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        }
+    }
+
+    /// Handles one line of input, including its trailing newline if it had one.
+    pub fn feed<W: Write>(&mut self, line: &str, output: &mut W) -> io::Result<()> {
+        let is_blank = line.trim().is_empty();
+
+        if self.buffer.is_empty() {
+            // No Monkey program starts with `:`, so commands cannot shadow code.
+            if let Some(command) = line.trim_start().strip_prefix(':') {
+                return run_command(command.trim_end(), &mut self.env, output, &self.options);
+            }
+        } else if is_blank && self.last_was_blank {
+            self.cancel();
+            return writeln!(output, "(input discarded)");
+        }
+
+        self.buffer.push_str(line);
+        self.last_was_blank = is_blank;
+
+        if is_incomplete(&self.buffer) {
+            return Ok(());
+        }
+
+        let source = std::mem::take(&mut self.buffer);
+        evaluate(&source, ORIGIN, &self.env, output, &self.options)
+    }
+
+    /// Discards any pending multi-line input.
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+        self.last_was_blank = false;
+    }
+
+    /// Ends the session, reporting whatever is still pending rather than dropping it silently.
+    pub fn finish<W: Write>(&mut self, output: &mut W) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let source = std::mem::take(&mut self.buffer);
+        evaluate(&source, ORIGIN, &self.env, output, &self.options)
+    }
+}
+
+/// Whether `source` stops partway through a statement: a bracket, string or comment is still open, or the
+/// first parse error is running into the end of the input, as in `let x =`.
+fn is_incomplete(source: &str) -> bool {
+    let mut l = Lexer::new(source);
+    let mut depth = 0;
+
+    loop {
+        match l.next_token().token {
+            Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBrace | Token::RBracket => depth -= 1,
+            Token::Error(LexError::UnterminatedString | LexError::UnterminatedComment) => return true,
+            Token::Eof => break,
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        // Too many closing brackets can never be completed, so let the parser report them.
+        return depth > 0;
+    }
+
+    match runner::parse(source) {
+        Err(RunError::Parse(errors)) => errors.first().is_some_and(|error| *error.found() == Token::Eof),
+        _ => false,
+    }
+}
+
+fn evaluate<W: Write>(source: &str, origin: &str, env: &Env, output: &mut W, options: &Options) -> io::Result<()> {
+    let (result, printed) = capture_output(|| {
+        runner::parse(source).and_then(|program| {
+            let value = runner::eval(&program, env)?;
+            // `let` evaluates to null; echoing that after every binding (or blank line) is just noise.
+            let echo = matches!(program.statements.last().map(|stmt| &stmt.kind), Some(kind) if !matches!(kind, StmtKind::Let(_)));
+            Ok(echo.then_some(value))
+        })
+    });
+    output.write_all(&printed)?;
+
+    match result {
+        Ok(Some(value)) => writeln!(output, "{}", value.inspect()),
+        Ok(None) => Ok(()),
+        Err(error) => write!(output, "{}", error.render(source, origin, options.color)),
+    }
+}
+
+fn run_command<W: Write>(command: &str, env: &mut Env, output: &mut W, options: &Options) -> io::Result<()> {
+    let (name, arg) = match command.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command, ""),
+    };
+
+    match name {
+        "tokens" => print_tokens(arg, output),
+        "ast" => match runner::parse(arg) {
+            Ok(program) => program.statements.iter().try_for_each(|stmt| writeln!(output, "{:#?}", stmt)),
+            Err(error) => write!(output, "{}", error.render(arg, ORIGIN, options.color)),
+        },
+        "bytecode" => match compile_in(arg, env) {
+            Ok(bytecode) => write!(output, "{}", disassemble(&bytecode)),
+            Err(error) => write!(output, "{}", error.render(arg, ORIGIN, options.color)),
+        },
+        "env" => env
+            .borrow()
+            .bindings()
+            .iter()
+            .try_for_each(|(name, value)| writeln!(output, "{} = {}", name, value.inspect())),
+        "reset" => {
+            *env = Environment::new();
+            Ok(())
+        }
+        "load" => match fs::read_to_string(arg) {
+            Ok(source) => evaluate(&source, arg, env, output, options),
+            Err(e) => report(&format!("cannot read {}: {}", arg, e), output, options),
+        },
+        "time" => {
+            let start = Instant::now();
+            evaluate(arg, ORIGIN, env, output, options)?;
+            writeln!(output, "took {:?}", start.elapsed())
+        }
+        "help" => writeln!(output, "{}", HELP),
+        _ => report(&format!("unknown command `:{}`, try `:help`", name), output, options),
+    }
+}
+
+/// Compiles `source` with the session's bindings declared as globals, so `:bytecode` can show code
+/// that uses them.
+fn compile_in(source: &str, env: &Env) -> Result<Bytecode, RunError> {
+    let program = runner::parse(source)?;
+
+    let mut symbol_table = SymbolTable::with_builtins();
+    for (name, _) in env.borrow().bindings() {
+        symbol_table.define(&name);
+    }
+
+    let mut compiler = Compiler::new_with_state(symbol_table, vec![]);
+    compiler.compile(&program).map_err(RunError::Compile)?;
+    Ok(compiler.bytecode())
+}
+
+fn print_tokens<W: Write>(source: &str, output: &mut W) -> io::Result<()> {
+    let mut l = Lexer::new(source);
+
+    loop {
+        let tok = l.next_token();
+        if tok.token == Token::Eof {
+            return Ok(());
+        }
+        writeln!(output, "{}:{}\t{:?}", tok.span.line, tok.span.col, tok.token)?;
+    }
+}
+
+fn report<W: Write>(message: &str, output: &mut W, options: &Options) -> io::Result<()> {
+    let diagnostic = Diagnostic::new(message.to_string(), None);
+    write!(output, "{}", render(&diagnostic, "", ORIGIN, options.color))
+}
+
+#[cfg(test)]
+mod repl_tests {
+    use std::fs;
+
+    use crate::repl::{is_incomplete, start};
+
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        start(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_start_reports_parse_errors() {
+        let mut output = Vec::new();
+
+        start("let = 5;\n".as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("error: expected identifier, found `=`\n --> <repl>:1:5\n"), "output: {}", output);
+    }
+
+    #[test]
+    fn test_start_evaluates_against_persistent_env() {
+        let input = "let x = 5;\nx * 2\n\"a\" + \"b\"\ny\nx\n";
+        let mut output = Vec::new();
+
+        start(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let expected = ">> >> 10
+>> \"ab\"
+>> error: identifier not found: y
+ --> <repl>:1:1
+  |
+1 | y
+  | ^
+  = help: bind it first with `let y = ...;`
+>> 5
+>> ";
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_puts_writes_to_session_output() {
+        assert_eq!(">> 1\ntwo\nnull\n>> null\n>> ", session("puts(1, \"two\");\nputs()\n"));
+    }
+
+    #[test]
+    fn test_tokens_and_ast_commands() {
+        assert_eq!(">> 1:1\tLet\n1:5\tIdent(\"x\")\n1:7\tAssign\n1:9\tInt(5)\n>> ", session(":tokens let x = 5\n"));
+
+        let output = session(":ast -a\n:ast let = 1\n");
+        assert!(output.contains("operator: Some(\n"), "output: {}", output);
+        assert!(output.contains("Negate,\n"), "output: {}", output);
+        assert!(output.contains("error: expected identifier, found `=`\n"), "output: {}", output);
+    }
+
+    #[test]
+    fn test_bytecode_command() {
+        let output = session("let a = 1; let b = 2; let c = 3;\n:bytecode -a * b + c\n:bytecode d\n");
+
+        let expected = ">> >> 0000 OpGetGlobal 0
+0003 OpMinus
+0004 OpGetGlobal 1
+0007 OpMul
+0008 OpGetGlobal 2
+0011 OpAdd
+0012 OpPop
+>> 0000 OpGetGlobal 3
+0003 OpPop
+";
+        assert!(output.starts_with(expected), "output: {}", output);
+    }
+
+    #[test]
+    fn test_env_and_reset_commands() {
+        let output = session("let b = \"two\";\nlet a = [1];\n:env\n:reset\n:env\na\n");
+
+        assert!(output.starts_with(">> >> >> a = [1]\nb = \"two\"\n>> >> >> error: identifier not found: a\n"), "output: {}", output);
+    }
+
+    #[test]
+    fn test_load_and_time_commands() {
+        let path = std::env::temp_dir().join(format!("repl-load-{}.mk", std::process::id()));
+        fs::write(&path, "let double = fn(x) { x * 2 };\n").unwrap();
+
+        let output = session(&format!(":load {}\n:time double(21)\n:load /no/such/file.mk\n", path.display()));
+        fs::remove_file(&path).unwrap();
+
+        assert!(output.starts_with(">> >> 42\ntook "), "output: {}", output);
+        assert!(output.contains(">> error: cannot read /no/such/file.mk: "), "output: {}", output);
+    }
+
+    #[test]
+    fn test_help_and_unknown_commands() {
+        assert!(session(":help\n").contains(":tokens <code>"));
+        assert!(session(":nope\n").contains("error: unknown command `:nope`, try `:help`\n"));
+    }
+
+    #[test]
+    fn test_multi_line_input() {
+        let input = "let add = fn(a, b) {\n  a +\n\n  b\n};\nadd(1,\n2)\n\"multi\nline\"\n";
+
+        assert_eq!(">> .. .. .. .. >> .. 3\n>> .. \"multi\\nline\"\n>> ", session(input));
+    }
+
+    #[test]
+    fn test_multi_line_input_can_be_discarded() {
+        let output = session("let x = [1,\n\n\nx\nlet y = (1\n");
+
+        assert!(output.starts_with(">> .. .. (input discarded)\n>> error: identifier not found: x\n"), "output: {}", output);
+        assert!(output.ends_with(">> .. error: expected `)`, found end of input\n --> <repl>:2:1\n  |\n2 | \n  | ^\n"), "output: {}", output);
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        let tests = vec![
+            ("fn(x) {", true),
+            ("foo(1, [2", true),
+            ("\"abc", true),
+            ("1 /* note", true),
+            ("let x =", true),
+            ("1 +", true),
+            ("if (x) { 1 } else", true),
+            ("let x = 1", false),
+            ("", false),
+            ("1 }", false),
+            ("let = 1; let y =", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, is_incomplete(input), "input: {:?}", input);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+use crate::compiler::{Bytecode, CompileError, Compiler};
+use crate::diagnostics::{render, Diagnostic};
+use crate::eval::environment::Env;
+use crate::eval::eval_program;
+use crate::eval::object::{Object, RuntimeError};
+use crate::lexer::Lexer;
+use crate::parser::ast::Program;
+use crate::parser::{ParseError, Parser};
+use crate::vm::Vm;
+
+/// Exit codes used by the command line runner, following BSD `sysexits.h`.
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_PARSE_ERROR: i32 = 65;
+pub const EXIT_NO_INPUT: i32 = 66;
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+pub const EXIT_CANT_CREATE: i32 = 73;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunError {
+    Parse(Vec<ParseError>),
+    Compile(CompileError),
+    Runtime(RuntimeError),
+}
+
+impl RunError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Parse(_) | RunError::Compile(_) => EXIT_PARSE_ERROR,
+            RunError::Runtime(_) => EXIT_RUNTIME_ERROR,
+        }
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            RunError::Parse(errors) => errors.iter().map(Diagnostic::from).collect(),
+            RunError::Compile(error) => vec![Diagnostic::from(error)],
+            RunError::Runtime(error) => vec![Diagnostic::from(error)],
+        }
+    }
+
+    /// Renders every diagnostic against `source`, see `diagnostics::render`.
+    pub fn render(&self, source: &str, origin: &str, color: bool) -> String {
+        self.diagnostics().iter().map(|diagnostic| render(diagnostic, source, origin, color)).collect()
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Parse(errors) => {
+                let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            RunError::Compile(error) => write!(f, "{}", error),
+            RunError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+/// Parses and evaluates `source` in `env`, returning the value of the last statement.
+pub fn run(source: &str, env: &Env) -> Result<Object, RunError> {
+    let program = parse(source)?;
+    eval(&program, env)
+}
+
+pub fn parse(source: &str) -> Result<Program, RunError> {
+    let mut parser = Parser::new(Lexer::new(source));
+    parser.parse_program().map_err(RunError::Parse)
+}
+
+pub fn eval(program: &Program, env: &Env) -> Result<Object, RunError> {
+    match eval_program(program, env) {
+        Object::Error(error) => Err(RunError::Runtime(error)),
+        value => Ok(value),
+    }
+}
+
+/// Like `run`, but compiles `source` to bytecode and runs it on the VM.
+pub fn run_vm(source: &str) -> Result<Object, RunError> {
+    let program = parse(source)?;
+    let bytecode = compile(&program)?;
+    Vm::new(bytecode).run().map_err(RunError::Runtime)
+}
+
+pub fn compile(program: &Program) -> Result<Bytecode, RunError> {
+    let mut compiler = Compiler::new();
+    compiler.compile(program).map_err(RunError::Compile)?;
+    Ok(compiler.bytecode())
+}
+
+#[cfg(test)]
+mod runner_tests {
+    use crate::eval::environment::Environment;
+    use crate::eval::object::Object;
+    use crate::runner::{run, run_vm, EXIT_PARSE_ERROR, EXIT_RUNTIME_ERROR};
+
+    #[test]
+    fn test_run_returns_last_value() {
+        let env = Environment::new();
+
+        assert_eq!(Ok(Object::Integer(6)), run("let x = 2;\nx * 3", &env));
+        assert_eq!(Ok(Object::Integer(7)), run("x + 5", &env));
+    }
+
+    #[test]
+    fn test_run_errors() {
+        let parse_error = run("let x = ;\nlet = 1;", &Environment::new()).unwrap_err();
+        assert_eq!(EXIT_PARSE_ERROR, parse_error.exit_code());
+        assert_eq!(2, parse_error.diagnostics().len());
+
+        let runtime_error = run("1;\nfoo + 1", &Environment::new()).unwrap_err();
+        assert_eq!(EXIT_RUNTIME_ERROR, runtime_error.exit_code());
+        assert_eq!(
+            "error: identifier not found: foo\n --> x.mk:2:1\n  |\n2 | foo + 1\n  | ^^^\n  = help: bind it first with `let foo = ...;`\n",
+            runtime_error.render("1;\nfoo + 1", "x.mk", false)
+        );
+    }
+
+    #[test]
+    fn test_run_vm() {
+        assert_eq!(Ok(Object::Integer(6)), run_vm("let x = 2;\nx * 3"));
+
+        // Names are looked up when the code using them runs, as in the evaluator.
+        assert_eq!(Ok(Object::Null), run_vm("let f = fn() { y };"));
+        let unbound = run_vm("let f = fn() { y };\nf()").unwrap_err();
+        assert_eq!(EXIT_RUNTIME_ERROR, unbound.exit_code());
+        assert_eq!(
+            "error: identifier not found: y\n --> x.mk:1:16\n  |\n1 | let f = fn() { y };\n  |                ^\n  = help: bind it first with `let y = ...;`\n",
+            unbound.render("let f = fn() { y };\nf()", "x.mk", false)
+        );
+
+        let runtime_error = run_vm("let f = fn(x) { x / 0 };\nf(1)").unwrap_err();
+        assert_eq!(EXIT_RUNTIME_ERROR, runtime_error.exit_code());
+        assert_eq!(
+            "error: division by zero\n --> x.mk:1:17\n  |\n1 | let f = fn(x) { x / 0 };\n  |                 ^^^^^\n",
+            runtime_error.render("let f = fn(x) { x / 0 };\nf(1)", "x.mk", false)
+        );
+    }
+}
@@ -1,13 +1,21 @@
 use std::fmt;
 use std::fmt::Formatter;
 
+use crate::lexer::LexError;
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub enum Token {
     Illegal,
     Eof,
 
+    Error(LexError),
+    /// Trivia: a `// line` or `/* block */` comment, including its delimiters. Only emitted by a
+    /// lexer built `with_comments`.
+    Comment(String),
+
     Ident(String),
     Int(i64),
+    Str(String),
     True,
     False,
 
@@ -26,11 +34,14 @@ pub enum Token {
 
     Comma,
     Semicolon,
+    Colon,
 
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
 
     Function,
     Let,
@@ -41,6 +52,67 @@ pub enum Token {
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "TokenType({:?})", self)
+        match self {
+            Token::Illegal => write!(f, "ILLEGAL"),
+            Token::Eof => write!(f, "EOF"),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Error(error) => write!(f, "{}", error),
+            Token::Comment(text) => write!(f, "{}", text),
+            Token::Int(value) => write!(f, "{}", value),
+            Token::Str(value) => write!(f, "{:?}", value),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::Assign => write!(f, "="),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Bang => write!(f, "!"),
+            Token::Asterisk => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Eq => write!(f, "=="),
+            Token::NotEq => write!(f, "!="),
+            Token::LT => write!(f, "<"),
+            Token::GT => write!(f, ">"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Colon => write!(f, ":"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBrace => write!(f, "{{"),
+            Token::RBrace => write!(f, "}}"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+            Token::Function => write!(f, "fn"),
+            Token::Let => write!(f, "let"),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::Return => write!(f, "return"),
+        }
+    }
+}
+
+/// Location of a token in the source: byte offsets `start..end` plus the 1-based line and
+/// column (in bytes) of `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Span {
+        Span { start, end, line, col }
+    }
+
+    /// Span covering `self` through the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end.max(self.end), ..self }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
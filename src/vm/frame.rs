@@ -0,0 +1,23 @@
+use std::rc::Rc;
+
+use crate::compiler::code::Instructions;
+use crate::eval::object::Closure;
+
+/// One function call in progress: the closure being run, the offset of its next instruction and
+/// the stack slot where its locals start.
+#[derive(Debug)]
+pub struct Frame {
+    pub closure: Rc<Closure>,
+    pub ip: usize,
+    pub base_pointer: usize,
+}
+
+impl Frame {
+    pub fn new(closure: Rc<Closure>, base_pointer: usize) -> Frame {
+        Frame { closure, ip: 0, base_pointer }
+    }
+
+    pub fn instructions(&self) -> &Instructions {
+        &self.closure.function.instructions
+    }
+}
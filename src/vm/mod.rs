@@ -0,0 +1,659 @@
+mod frame;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+use crate::compiler::code::{read_u16, Capture, Opcode};
+use crate::compiler::Bytecode;
+use crate::eval::builtins::BUILTINS;
+use crate::eval::object::{Closure, CompiledFunction, HashKey, Object, RuntimeError, Upvalue};
+use crate::eval::{eval_index_expr, eval_infix_expr, eval_prefix_expr};
+use crate::parser::ast::{InfixOperator, PrefixOperator};
+use frame::Frame;
+
+/// Most values the operand stack holds at once, locals of every active call included.
+pub const STACK_SIZE: usize = 2048;
+/// Deepest nesting of calls before the VM reports a stack overflow.
+pub const MAX_FRAMES: usize = 1024;
+
+/// Runs compiled bytecode on an operand stack. Operators, indexing and builtins share their
+/// implementation with the evaluator, so both engines give the same results and error messages.
+pub struct Vm {
+    constants: Vec<Object>,
+    stack: Vec<Object>,
+    /// Values of the global slots; `None` until the program first stores to the slot.
+    globals: Vec<Option<Object>>,
+    global_names: Vec<String>,
+    frames: Vec<Frame>,
+    /// Captured variables whose stack slot is still live, closed when their call returns.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    last_popped: Object,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Vm {
+        Vm::new_with_globals(bytecode, vec![])
+    }
+
+    /// Runs against the globals left by an earlier run, for bytecode compiled with the symbol
+    /// table of that run.
+    pub fn new_with_globals(bytecode: Bytecode, globals: Vec<Option<Object>>) -> Vm {
+        let main = CompiledFunction {
+            instructions: bytecode.instructions,
+            num_locals: 0,
+            params: vec![],
+            captures: vec![],
+            source_map: bytecode.source_map,
+        };
+        let main = Rc::new(Closure { function: Rc::new(main), free: vec![] });
+
+        Vm {
+            constants: bytecode.constants,
+            stack: Vec::with_capacity(STACK_SIZE),
+            globals,
+            global_names: bytecode.globals,
+            frames: vec![Frame::new(main, 0)],
+            open_upvalues: vec![],
+            last_popped: Object::Null,
+        }
+    }
+
+    pub fn into_globals(self) -> Vec<Option<Object>> {
+        self.globals
+    }
+
+    /// Runs the program to the end and returns its value: that of the last expression statement,
+    /// or of a `return` at the top level. Errors carry the span of the expression whose
+    /// instruction failed.
+    pub fn run(&mut self) -> Result<Object, RuntimeError> {
+        while self.frame().ip < self.frame().instructions().len() {
+            let ip = self.frame().ip;
+
+            match self.step() {
+                Ok(None) => {}
+                Ok(Some(value)) => return Ok(value),
+                Err(mut error) => {
+                    if error.span.is_none() {
+                        error.span = self.frame().closure.function.source_map.lookup(ip);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(mem::replace(&mut self.last_popped, Object::Null))
+    }
+
+    /// Executes one instruction. Returns the program's value once a top level `return` ends it.
+    fn step(&mut self) -> Result<Option<Object>, RuntimeError> {
+        let byte = self.read_u8() as u8;
+        let op = Opcode::try_from(byte).map_err(|byte| RuntimeError::new(format!("unknown opcode: {}", byte)))?;
+
+        match op {
+            Opcode::Constant => {
+                let index = self.read_u16();
+                self.push(self.constants[index].clone())?;
+            }
+            Opcode::Pop => self.last_popped = self.pop()?,
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::GreaterThan
+            | Opcode::LessThan => {
+                let operator = match op {
+                    Opcode::Add => InfixOperator::Add,
+                    Opcode::Sub => InfixOperator::Sub,
+                    Opcode::Mul => InfixOperator::Mul,
+                    Opcode::Div => InfixOperator::Div,
+                    Opcode::Equal => InfixOperator::Equals,
+                    Opcode::NotEqual => InfixOperator::NotEquals,
+                    Opcode::GreaterThan => InfixOperator::GreaterThan,
+                    _ => InfixOperator::LessThan,
+                };
+
+                let right = self.pop()?;
+                let left = self.pop()?;
+                self.push_result(eval_infix_expr(&operator, left, right))?;
+            }
+            Opcode::True => self.push(Object::Boolean(true))?,
+            Opcode::False => self.push(Object::Boolean(false))?,
+            Opcode::Null => self.push(Object::Null)?,
+            Opcode::Minus => {
+                let right = self.pop()?;
+                self.push_result(eval_prefix_expr(&PrefixOperator::Negate, right))?;
+            }
+            Opcode::Bang => {
+                let right = self.pop()?;
+                self.push_result(eval_prefix_expr(&PrefixOperator::Not, right))?;
+            }
+            Opcode::JumpNotTruthy => {
+                let target = self.read_u16();
+                if !self.pop()?.is_truthy() {
+                    self.frame_mut().ip = target;
+                }
+            }
+            Opcode::Jump => {
+                let target = self.read_u16();
+                self.frame_mut().ip = target;
+            }
+            Opcode::GetGlobal => {
+                let index = self.read_u16();
+                match self.globals.get(index).cloned().flatten() {
+                    Some(value) => self.push(value)?,
+                    None => return Err(RuntimeError::new(format!("identifier not found: {}", self.global_names[index]))),
+                }
+            }
+            Opcode::SetGlobal => {
+                let index = self.read_u16();
+                if index >= self.globals.len() {
+                    self.globals.resize(index + 1, None);
+                }
+                self.globals[index] = Some(self.pop()?);
+            }
+            Opcode::GetLocal => {
+                let slot = self.frame().base_pointer + self.read_u8();
+                self.push(self.stack[slot].clone())?;
+            }
+            Opcode::SetLocal => {
+                let slot = self.frame().base_pointer + self.read_u8();
+                self.stack[slot] = self.pop()?;
+            }
+            Opcode::GetBuiltin => {
+                let index = self.read_u8();
+                self.push(Object::Builtin(BUILTINS[index]))?;
+            }
+            Opcode::GetFree => {
+                let index = self.read_u8();
+                let value = match &*self.frame().closure.free[index].borrow() {
+                    Upvalue::Open(slot) => self.stack[*slot].clone(),
+                    Upvalue::Closed(value) => value.clone(),
+                };
+                self.push(value)?;
+            }
+            Opcode::CurrentClosure => self.push(Object::Closure(Rc::clone(&self.frame().closure)))?,
+            Opcode::Array => {
+                let count = self.read_u16();
+                let elements = self.pop_many(count)?;
+                self.push(Object::Array(elements))?;
+            }
+            Opcode::Hash => {
+                let count = self.read_u16();
+                let entries = self.pop_many(count)?;
+
+                let mut hash = HashMap::with_capacity(count / 2);
+                let mut entries = entries.into_iter();
+                while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                    hash.insert(HashKey::try_from(&key)?, value);
+                }
+                self.push(Object::Hash(hash))?;
+            }
+            Opcode::Index => {
+                let index = self.pop()?;
+                let target = self.pop()?;
+                self.push_result(eval_index_expr(target, index))?;
+            }
+            Opcode::Call => {
+                let count = self.read_u8();
+                self.call(count)?;
+            }
+            Opcode::ReturnValue => {
+                let value = self.pop()?;
+                return self.return_from_call(value);
+            }
+            Opcode::Return => return self.return_from_call(Object::Null),
+            Opcode::Closure => {
+                let index = self.read_u16();
+
+                let function = match &self.constants[index] {
+                    Object::CompiledFunction(function) => Rc::clone(function),
+                    other => return Err(RuntimeError::new(format!("not a function: {}", other.type_name()))),
+                };
+                let free = function.captures.iter().map(|capture| self.capture(*capture)).collect();
+                self.push(Object::Closure(Rc::new(Closure { function, free })))?;
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Finds the variable a closure being created captures. Closures that capture the same local
+    /// of a call share it, so they all see the call rebind it.
+    fn capture(&mut self, capture: Capture) -> Rc<RefCell<Upvalue>> {
+        match capture {
+            Capture::Local(index) => {
+                let slot = self.frame().base_pointer + index;
+                let open = self.open_upvalues.iter().find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+                if let Some(upvalue) = open {
+                    return Rc::clone(upvalue);
+                }
+
+                let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+                self.open_upvalues.push(Rc::clone(&upvalue));
+                upvalue
+            }
+            Capture::Free(index) => Rc::clone(&self.frame().closure.free[index]),
+            Capture::Function => {
+                let closure = Object::Closure(Rc::clone(&self.frame().closure));
+                Rc::new(RefCell::new(Upvalue::Closed(closure)))
+            }
+        }
+    }
+
+    /// Calls the function below the top `count` values of the stack, which are its arguments.
+    fn call(&mut self, count: usize) -> Result<(), RuntimeError> {
+        if self.stack.len() < self.floor() + count + 1 {
+            return Err(underflow());
+        }
+        let callee = self.stack[self.stack.len() - 1 - count].clone();
+
+        match callee {
+            Object::Closure(closure) => {
+                let function = &closure.function;
+                if function.params.len() != count {
+                    return Err(RuntimeError::new(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        function.params.len(),
+                        count
+                    )));
+                }
+
+                let base_pointer = self.stack.len() - count;
+                let top = base_pointer + function.num_locals;
+                if self.frames.len() >= MAX_FRAMES || top > STACK_SIZE {
+                    return Err(RuntimeError::new("stack overflow".to_string()));
+                }
+
+                // Parameters are the first locals; the rest start out null.
+                self.stack.resize(top, Object::Null);
+                self.frames.push(Frame::new(closure, base_pointer));
+                Ok(())
+            }
+            Object::Builtin(builtin) => {
+                let args = self.stack.split_off(self.stack.len() - count);
+                self.stack.pop();
+                self.push_result((builtin.func)(args))
+            }
+            other => Err(RuntimeError::new(format!("not a function: {}", other.type_name()))),
+        }
+    }
+
+    /// Leaves the current call, replacing the callee and its locals with `value`. A return from
+    /// the top level ends the program instead.
+    fn return_from_call(&mut self, value: Object) -> Result<Option<Object>, RuntimeError> {
+        if self.frames.len() == 1 {
+            return Ok(Some(value));
+        }
+
+        let frame = self.frames.pop().expect("a call frame is active");
+
+        // Captured locals of the call outlive it with the values they had last.
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= frame.base_pointer => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        self.stack.truncate(frame.base_pointer - 1);
+        self.push(value)?;
+        Ok(None)
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("the main frame is never popped")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("the main frame is never popped")
+    }
+
+    fn read_u8(&mut self) -> usize {
+        let frame = self.frame_mut();
+        let value = frame.instructions()[frame.ip];
+        frame.ip += 1;
+        value as usize
+    }
+
+    fn read_u16(&mut self) -> usize {
+        let frame = self.frame_mut();
+        let value = read_u16(&frame.instructions()[frame.ip..]);
+        frame.ip += 2;
+        value as usize
+    }
+
+    fn push(&mut self, object: Object) -> Result<(), RuntimeError> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err(RuntimeError::new("stack overflow".to_string()));
+        }
+
+        self.stack.push(object);
+        Ok(())
+    }
+
+    /// Pushes the result of an operation shared with the evaluator, which reports failure with
+    /// an error object.
+    fn push_result(&mut self, result: Object) -> Result<(), RuntimeError> {
+        match result {
+            Object::Error(error) => Err(error),
+            value => self.push(value),
+        }
+    }
+
+    /// Lowest stack slot the current call may pop: the ones below hold its locals and the
+    /// values of the calls that are waiting for it.
+    fn floor(&self) -> usize {
+        let frame = self.frame();
+        frame.base_pointer + frame.closure.function.num_locals
+    }
+
+    fn pop(&mut self) -> Result<Object, RuntimeError> {
+        if self.stack.len() <= self.floor() {
+            return Err(underflow());
+        }
+
+        self.stack.pop().ok_or_else(underflow)
+    }
+
+    /// Pops the top `count` values, keeping their order.
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Object>, RuntimeError> {
+        if self.stack.len() < self.floor() + count {
+            return Err(underflow());
+        }
+
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+}
+
+/// The compiler keeps the stack balanced, so only malformed bytecode takes more values than a call
+/// has pushed.
+fn underflow() -> RuntimeError {
+    RuntimeError::new("stack underflow".to_string())
+}
+
+#[cfg(test)]
+mod vm_tests {
+    use std::collections::HashMap;
+
+    use crate::compiler::Compiler;
+    use crate::diagnostics::Diagnostic;
+    use crate::eval::builtins::capture_output;
+    use crate::eval::environment::Environment;
+    use crate::eval::object::{HashKey, Object, RuntimeError};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::runner;
+    use crate::token::Span;
+    use crate::vm::Vm;
+
+    fn run(input: &str) -> Result<Object, RuntimeError> {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+
+        let mut compiler = Compiler::new();
+        if let Err(e) = compiler.compile(&program) {
+            panic!("input: {}, compile error: {}", input, e);
+        }
+        Vm::new(compiler.bytecode()).run()
+    }
+
+    fn assert_runs(test_cases: Vec<(&str, Object)>) {
+        for (input, expected) in test_cases {
+            assert_eq!(Ok(expected), run(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        assert_runs(vec![
+            ("1", Object::Integer(1)),
+            ("1 + 2", Object::Integer(3)),
+            ("4 / 2 * 3 - 1", Object::Integer(5)),
+            ("5 * (2 + 10)", Object::Integer(60)),
+            ("-50 + 100 + -50", Object::Integer(0)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+        ]);
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        assert_runs(vec![
+            ("1 < 2", Object::Boolean(true)),
+            ("1 > 2", Object::Boolean(false)),
+            ("1 == 1", Object::Boolean(true)),
+            ("true != false", Object::Boolean(true)),
+            ("(1 < 2) == true", Object::Boolean(true)),
+            ("!5", Object::Boolean(false)),
+            ("!!5", Object::Boolean(true)),
+            ("!(if (false) { 5; })", Object::Boolean(true)),
+        ]);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        assert_runs(vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1) { let x = 1; }", Object::Null),
+            ("if (true) { }", Object::Null),
+            ("if ((if (false) { 10 })) { 10 } else { 20 }", Object::Integer(20)),
+        ]);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        assert_runs(vec![
+            ("let one = 1; one", Object::Integer(1)),
+            ("let one = 1; let two = one + one; one + two", Object::Integer(3)),
+            ("let x = 1; let x = x + 1; x", Object::Integer(2)),
+            ("let x = 1;", Object::Null),
+            ("", Object::Null),
+        ]);
+    }
+
+    #[test]
+    fn test_collections() {
+        let hash: HashMap<HashKey, Object> = vec![
+            (HashKey::Integer(1), Object::Integer(2)),
+            (HashKey::Str("a".to_string()), Object::Integer(12)),
+        ].into_iter().collect();
+
+        assert_runs(vec![
+            (r#""mon" + "key""#, Object::Str("monkey".to_string())),
+            ("[]", Object::Array(vec![])),
+            ("[1 + 2, 3 * 4][1]", Object::Integer(12)),
+            ("[1, 2, 3][-1]", Object::Integer(3)),
+            (r#"{1: 2, "a": 3 * 4}"#, Object::Hash(hash)),
+            ("{1: 1, 1: 2}[1]", Object::Integer(2)),
+            ("{}[0]", Object::Null),
+        ]);
+    }
+
+    #[test]
+    fn test_function_calls() {
+        assert_runs(vec![
+            ("let f = fn() { 5 + 10; }; f();", Object::Integer(15)),
+            ("let f = fn() { return 99; 100; }; f();", Object::Integer(99)),
+            ("let f = fn() { }; f();", Object::Null),
+            ("let f = fn() { return; }; f();", Object::Null),
+            ("let sum = fn(a, b) { let c = a + b; c; }; sum(1, 2) + sum(3, 4);", Object::Integer(10)),
+            ("let g = 50; let f = fn() { let g = 1; g }; f() + g", Object::Integer(51)),
+            ("let f = fn() { 1 }; let g = fn() { f }; g()()", Object::Integer(1)),
+            ("return 1; 2", Object::Integer(1)),
+            ("if (true) { return 3; } 4", Object::Integer(3)),
+        ]);
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        assert_runs(vec![
+            (r#"len("four")"#, Object::Integer(4)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("first(rest(push([1, 2], 3)))", Object::Integer(2)),
+            ("last([])", Object::Null),
+            ("let len = fn(x) { 42 }; len([1])", Object::Integer(42)),
+        ]);
+    }
+
+    #[test]
+    fn test_closures() {
+        assert_runs(vec![
+            ("let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)", Object::Integer(5)),
+            ("let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3)", Object::Integer(6)),
+            ("let f = fn(a) { let b = a * 2; fn() { let c = 1; fn() { a + b + c } } }; f(2)()()", Object::Integer(7)),
+            (
+                "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15)",
+                Object::Integer(610),
+            ),
+            (
+                "let wrapper = fn() { let count = fn(x) { if (x == 0) { 0 } else { count(x - 1) } }; count(3) }; wrapper()",
+                Object::Integer(0),
+            ),
+            ("let curry = fn(f) { fn(a) { fn(b) { f(a, b) } } }; curry(fn(a, b) { a - b })(10)(3)", Object::Integer(7)),
+            (
+                "let counter = fn(n) { {\"value\": n, \"next\": fn() { counter(n + 1) }} }; counter(0)[\"next\"]()[\"next\"]()[\"value\"]",
+                Object::Integer(2),
+            ),
+            ("let x = 1; let f = fn() { let x = 2; fn() { x } }; f()() + x", Object::Integer(3)),
+            // Captured locals are shared with the call that owns them until it returns, then keep
+            // their last value.
+            ("let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g() }; f()", Object::Integer(2)),
+            ("let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g }; f()()", Object::Integer(2)),
+            ("let f = fn(x) { let g = fn() { fn() { x } }; let x = x * 10; g()() }; f(3)", Object::Integer(30)),
+            ("let f = fn() { let x = 1; let a = fn() { x }; let b = fn() { x * 10 }; let x = 2; a() + b() }; f()", Object::Integer(22)),
+        ]);
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let test_cases: Vec<(&str, &str, Span)> = vec![
+            ("1 + true", "type mismatch: INTEGER + BOOLEAN", Span::new(0, 8, 1, 1)),
+            ("let f = fn() { 1 / 0 };\nf()", "division by zero", Span::new(15, 20, 1, 16)),
+            ("fn(a) { a }(1, 2)", "wrong number of arguments: want=1, got=2", Span::new(0, 17, 1, 1)),
+            ("5(1)", "not a function: INTEGER", Span::new(0, 4, 1, 1)),
+            ("len(1)", "argument to `len` not supported, got INTEGER", Span::new(0, 6, 1, 1)),
+            ("[1][-true]", "unknown operator: -BOOLEAN", Span::new(4, 9, 1, 5)),
+            // The VM checks keys once the whole literal is built, so the error covers all of it.
+            ("{1: 2, [3]: 4}", "unusable as hash key: ARRAY", Span::new(0, 14, 1, 1)),
+            ("let f = fn() { f() }; f()", "stack overflow", Span::new(15, 18, 1, 16)),
+        ];
+
+        for (input, message, span) in test_cases {
+            let error = run(input).unwrap_err();
+            assert_eq!(message, error.message, "input: {}", input);
+            assert_eq!(Some(span), error.span, "input: {}", input);
+        }
+    }
+
+    /// Programs covering the whole language, run by both engines.
+    const PROGRAMS: &[&str] = &[
+        "5 + 5 + 5 + 5 - 10",
+        "(5 + 10 * 2 + 15 / 3) * 2 + -10",
+        "9223372036854775807 + 1",
+        "1 < 2 == 2 > 1",
+        "!-5",
+        "true == false",
+        "let a = 5; let b = a; let c = a + b + 5; c;",
+        "let a = 1; let a = a * 10;",
+        "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+        "if (1 > 2) { 10 }",
+        "if (false) { 1 } else { let y = 2; }",
+        "9; return 2 * 5; 9;",
+        "return;",
+        r#"let greet = fn(name) { "Hi, " + name }; greet("\u{1F600}\n")"#,
+        r#""a" == "a""#,
+        r#"[1, true, ["a"], fn(x) { x }]"#,
+        "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+        "[[1, 2], [3, 4]][1][0]",
+        r#"let two = "two"; {"one": 10 - 9, two: 1 + 1, "thr" + "ee": 6 / 2, 4: 4, true: 5, false: 6}"#,
+        r#"let key = "foo"; {"foo": 5}[key]"#,
+        r#"{"a": 1, "a": 2}["a"]"#,
+        r#"len("héllo") + len({"a": 1})"#,
+        "let a = [1]; push(a, 2); a",
+        "rest([])",
+        "puts(1, 2)",
+        "let len = fn(x) { 42 }; len([1])",
+        "let early = fn() { return 1; 2; }; early() + 10;",
+        "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+        "fn(x) { x; }(5)",
+        "let f = fn() { let x = 1; }; f()",
+        "let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2); [addTwo(3), adder(10)(1)]",
+        "let curry = fn(f) { fn(a) { fn(b) { f(a, b) } } }; curry(fn(a, b) { a * b })(6)(7)",
+        "let compose = fn(f, g) { fn(x) { g(f(x)) } }; compose(fn(x) { x + 1 }, fn(x) { x * 2 })(5)",
+        "let counter = fn(n) { fn() { counter(n + 1) } }; let c = counter(0); c()()",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(12)",
+        "let x = 1; let f = fn(x) { x * 10 }; f(5) + x",
+        "let x = 1; let f = fn() { x }; let x = 2; f()",
+        "let f = fn() { let x = 1; let g = fn() { x }; let x = 2; [g(), g] }; let r = f(); [r[0], r[1]()]",
+        "let f = fn(n) { let g = fn() { fn() { n } }; let n = n + 1; g }; f(1)()()",
+        "let x = 1; let f = fn() { let x = 2; fn() { x } }; f()() + x",
+        "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * x })",
+        "let reduce = fn(arr, initial, f) { let iter = fn(arr, result) { if (len(arr) == 0) { result } else { iter(rest(arr), f(result, first(arr))) } }; iter(arr, initial) }; reduce([1, 2, 3, 4], 0, fn(a, b) { a + b })",
+        "let f = fn(x) { x }; f == f",
+        "fn() { 1 } == fn() { 1 }",
+        "let g = fn() { fn() { 1 } }; [g() == g(), g() != g()]",
+        "let f = fn() { if (true) { return [1]; } 2 }; f()[0]",
+        "let x = if (true) { return 5; }; puts(x); 10",
+        "let f = fn() { let x = if (true) { return 5; }; 10 }; puts(f())",
+        "puts(1 + if (true) { return 2; })",
+        "let f = fn() { [1, if (true) { return 2; }, 3] }; f()",
+        "let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } }; let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } }; isEven(10)",
+        "let f = fn() { g() }; let g = fn() { 1 }; f()",
+        "let f = fn() { let h = fn() { g() }; let g = fn() { 7 }; h() }; f()",
+        "let f = fn() { let g = fn() { y }; let y = 5; g() }; f()",
+        "let x = 1; let f = fn() { let y = x; let x = 5; [y, fn() { x }()] }; f()",
+        "let f = fn(b) { let g = fn() { z }; if (b) { let z = 2; }; g() }; f(true)",
+        "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; let g = f; let f = fn(n) { 42 }; g(3)",
+        "let x = fn() { x }; let y = x; let x = 3; y()",
+        "let w = fn() { let f = fn() { f }; let g = f; let f = 1; g() }; w()",
+        "let w = fn() { let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(3) }; w()",
+        "if (false) { y } else { 2 }",
+        "let f = fn() { y }; 5",
+        // Errors.
+        "5 + true; 5;",
+        "-true",
+        "true + false;",
+        "1 < 2 < 3",
+        "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
+        "foobar",
+        "1 + 2 * foo",
+        "fn() { y }()",
+        "let f = fn() { g() }; f(); let g = fn() { 1 };",
+        "let f = fn() { let a = y; let y = 1; a }; f()",
+        "10 / 0",
+        r#""Hello" - "World""#,
+        "[1, 2, 3][3]",
+        "[1][true]",
+        "1[0]",
+        r#"len("one", "two")"#,
+        "first(1)",
+        r#"{"name": "Monkey"}[fn(x) { x }];"#,
+        "5(1)",
+        "fn(x, y) { x }(1)",
+        "let f = fn() { 1 / 0 };\nf()",
+        "let x = 5;\nx + true",
+    ];
+
+    /// What a run produced, in a form both engines can be compared on: the value as the REPL
+    /// prints it, or the exit code and the error messages and spans.
+    fn outcome(result: Result<Object, runner::RunError>) -> Result<String, (i32, Vec<Diagnostic>)> {
+        result.map(|value| value.inspect()).map_err(|error| (error.exit_code(), error.diagnostics()))
+    }
+
+    #[test]
+    fn test_matches_evaluator() {
+        for source in PROGRAMS {
+            let expected = capture_output(|| outcome(runner::run(source, &Environment::new())));
+            assert_eq!(expected, capture_output(|| outcome(runner::run_vm(source))), "source: {:?}", source);
+        }
+    }
+}
@@ -0,0 +1,8 @@
+let   x=5
+let y = x*2+1;let z=(x+y)*  2;
+
+
+let name="monkey\tbusiness";
+   puts(name,x,  y);
+-x;!true
+return x==y
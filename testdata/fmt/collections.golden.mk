@@ -0,0 +1,8 @@
+let people = [{"name": "Alice", "age": 24}, {"name": "Anna", "age": 28}];
+people[0]["name"];
+let h = {true: 1, 2: "two", "three": [3]};
+-h[2];
+[1, 2, 3][1 + 1];
+if (x) {} else {
+    y;
+};
@@ -0,0 +1,18 @@
+let f = fn() {
+    1;
+    // end of body
+};
+let g = fn() {
+    // only comment
+};
+if (x) {
+    1;
+}; // trailing if
+if (y) {
+    2; // two
+} else {
+    3;
+}; // three
+let h = fn() {
+    4;
+}; // after h
@@ -0,0 +1,13 @@
+// Greets people.
+/* Block comments /* nest */
+   and span lines. */
+let greet = fn(name) {
+    // Build the message.
+    let message = "Hi, " + name; // not "Hello"
+
+    message; /* implicit return */
+}; // end of greet
+
+let names = ["Ann", "Bob"]; // first
+greet(names[0]); // trailing
+// The end.
@@ -0,0 +1,15 @@
+// Greets people.
+/* Block comments /* nest */
+   and span lines. */
+let greet = fn(name) {
+  // Build the message.
+  let message = "Hi, " + name; // not "Hello"
+
+  message /* implicit return */
+};    // end of greet
+
+
+let names = ["Ann", // first
+  "Bob"];
+greet(names[0]) // trailing
+// The end.
@@ -0,0 +1,23 @@
+let fib = fn(n) {
+    if (n < 2) {
+        return n;
+    };
+    fib(n - 1) + fib(n - 2);
+};
+let map = fn(arr, f) {
+    let iter = fn(arr, acc) {
+        if (len(arr) == 0) {
+            acc;
+        } else {
+            iter(rest(arr), push(acc, f(first(arr))));
+        };
+    };
+    iter(arr, []);
+};
+map([1, 2, 3], fn(x) {
+    x * x;
+});
+let noop = fn() {};
+fn(a, b) {
+    a - (b - a);
+}(1, 2);
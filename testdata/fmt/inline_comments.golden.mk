@@ -0,0 +1,23 @@
+let xs = [1, /* two */ 2, /* three */ 3];
+let h = {"a": /* one */ 1};
+let f = fn(x) {
+    if (x) {
+        1;
+    } /* otherwise */ else {
+        2;
+    };
+};
+if (a) {
+    1;
+} // not a
+else {
+    2;
+};
+if (b) {
+    1;
+} // own line
+else {
+    2;
+}; // after
+let s = add(1, 2); // first
+/* second */